@@ -0,0 +1,193 @@
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    thread,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    decentralised_identifier::{DecentralizedIdentifier, DecentralizedIdentifierError},
+    error::{CoseVerificationError, CwtValidationError, NzcpError},
+    pass::{Credential, VerifiedPass},
+    payload::{
+        barcode::QrBarcode,
+        cose::{
+            signature::{verify::VerifyingKey, CoseSignStructure, CoseSignature, SignatureAlgorithm},
+            CoseStructure,
+        },
+        cwt::validation::Leeway,
+    },
+};
+
+/// The issuer DID and key ID of the key a pass claims to be signed with.
+type KeyId = (String, String);
+
+/// The result of resolving each distinct key needed by a batch.
+pub(crate) type ResolvedKeys = HashMap<KeyId, Result<VerifyingKey, DecentralizedIdentifierError>>;
+
+/// A batch of decoded barcodes waiting on their issuers' keys, so that each key is resolved once for the whole batch
+/// however many passes were signed with it.
+pub(crate) struct PendingBatch<'a, P> {
+    items: Vec<Result<DecodedBarcode<P>, NzcpError>>,
+    trusted_issuers: &'a [DecentralizedIdentifier<'a>],
+    allowed_algorithms: &'a [SignatureAlgorithm],
+    lenient: bool,
+    now: DateTime<Utc>,
+    leeway: Leeway,
+}
+
+/// A barcode decoded once, keeping the signed segments to verify and the outcome of validating its claims.
+struct DecodedBarcode<P> {
+    key_id: KeyId,
+    algorithm: SignatureAlgorithm,
+    protected_headers: Vec<u8>,
+    cwt_claims: Vec<u8>,
+    signature: Vec<u8>,
+    /// Only returned once the signature is verified, so an invalid signature is reported before invalid claims.
+    validated: Result<VerifiedPass<P>, CwtValidationError>,
+}
+
+impl<'a, P: Credential> PendingBatch<'a, P> {
+    /// An empty batch, which normalises barcodes first if `lenient` and validates their claims as at `now`.
+    pub(crate) fn new(
+        trusted_issuers: &'a [DecentralizedIdentifier<'a>],
+        allowed_algorithms: &'a [SignatureAlgorithm],
+        lenient: bool,
+        now: DateTime<Utc>,
+        leeway: Leeway,
+    ) -> Self {
+        PendingBatch {
+            items: Vec::new(),
+            trusted_issuers,
+            allowed_algorithms,
+            lenient,
+            now,
+            leeway,
+        }
+    }
+
+    /// Decode a barcode, checking its issuer is trusted, its signature algorithm allowed and its claims valid.
+    pub(crate) fn push(&mut self, uri: &str) {
+        let decoded = self.decode(uri);
+        self.items.push(decoded);
+    }
+
+    /// The distinct keys needed to verify the batch.
+    pub(crate) fn keys_to_resolve(&self) -> Vec<(DecentralizedIdentifier<'_>, &str)> {
+        let mut seen = HashSet::new();
+
+        self.items
+            .iter()
+            .filter_map(|item| item.as_ref().ok())
+            .filter(|decoded| seen.insert(&decoded.key_id))
+            .map(|DecodedBarcode { key_id: (did, kid), .. }| {
+                let issuer = DecentralizedIdentifier::parse(did).expect("decoded issuers are did:web DIDs");
+                (issuer, kid.as_str())
+            })
+            .collect()
+    }
+
+    fn decode(&self, uri: &str) -> Result<DecodedBarcode<P>, NzcpError> {
+        let (barcode, normalisations) = if self.lenient {
+            QrBarcode::parse_lenient(uri)?
+        }
+        else {
+            (uri.parse()?, Vec::new())
+        };
+        let cose = CoseStructure::<P>::from_slice(&barcode.cbor)?;
+        cose.verify_algorithm(self.allowed_algorithms)?;
+        let issuer = cose.cwt_claims().verify_issuer(self.trusted_issuers)?;
+        let key_id = (issuer.did(), cose.kid().to_owned());
+
+        let algorithm = cose.protected_headers().algorithm;
+        let signature = cose.signature();
+        let (protected_headers, cwt_claims, signature) = (
+            signature.protected_headers_raw.to_vec(),
+            signature.cwt_claims_raw.to_vec(),
+            signature.bytes.to_vec(),
+        );
+        let kid = cose.kid();
+        let validated = cose
+            .into_cwt_claims()
+            .validated_pass(kid, self.now, self.leeway)
+            .map(|pass| VerifiedPass { normalisations, ..pass });
+
+        Ok(DecodedBarcode {
+            key_id,
+            algorithm,
+            protected_headers,
+            cwt_claims,
+            signature,
+            validated,
+        })
+    }
+}
+
+impl<P: Credential + Send> PendingBatch<'_, P> {
+    /// Verify the signatures in parallel across the available cores, returning the results in the order the barcodes
+    /// were given.
+    pub(crate) fn finish(self, keys: ResolvedKeys) -> Vec<Result<VerifiedPass<P>, NzcpError>> {
+        finish(self.items, keys)
+    }
+}
+
+impl<P: Credential + Send + 'static> PendingBatch<'_, P> {
+    /// Verify the signatures as [`PendingBatch::finish`] does, on another thread so as not to block an async executor.
+    pub(crate) async fn finish_in_background(self, keys: ResolvedKeys) -> Vec<Result<VerifiedPass<P>, NzcpError>> {
+        let items = self.items;
+        let (sender, receiver) = futures_channel::oneshot::channel();
+        thread::spawn(move || sender.send(finish(items, keys)));
+
+        receiver.await.expect("verification panicked")
+    }
+}
+
+fn finish<P: Credential + Send>(
+    items: Vec<Result<DecodedBarcode<P>, NzcpError>>,
+    keys: ResolvedKeys,
+) -> Vec<Result<VerifiedPass<P>, NzcpError>> {
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size = items.len().div_ceil(threads).max(1);
+
+    let mut items = items.into_iter();
+    let chunks = std::iter::from_fn(|| Some(items.by_ref().take(chunk_size).collect::<Vec<_>>()))
+        .take_while(|chunk| !chunk.is_empty());
+    let keys = &keys;
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = chunks
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .into_iter()
+                        .map(|item| item.and_then(|decoded| verify(decoded, keys)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("verification panicked"))
+            .collect()
+    })
+}
+
+fn verify<P>(decoded: DecodedBarcode<P>, keys: &ResolvedKeys) -> Result<VerifiedPass<P>, NzcpError> {
+    let verifying_key = keys
+        .get(&decoded.key_id)
+        .expect("every key in the batch is resolved")
+        .clone()
+        .map_err(CoseVerificationError::from)?;
+
+    let signature = CoseSignature {
+        bytes: &decoded.signature,
+        sign_structure: CoseSignStructure::Sign1,
+        protected_headers_raw: &decoded.protected_headers,
+        cwt_claims_raw: &decoded.cwt_claims,
+    };
+    signature.verify(decoded.algorithm, &verifying_key)?;
+
+    Ok(decoded.validated?)
+}
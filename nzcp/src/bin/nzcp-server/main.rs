@@ -0,0 +1,431 @@
+//! The `nzcp-server` HTTP service, for verifying passes from backends written in other languages.
+
+use std::{convert::Infallible, env, net::SocketAddr, process::ExitCode, sync::Arc, time::Duration};
+
+use hyper::{
+    body::HttpBody,
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use nzcp::{
+    error::{CoseVerificationError, DecentralizedIdentifierError, ErrorCategory, NzcpError},
+    DecentralizedIdentifier, DidWebProxyResolver, DidWebResolver, KeyCache, KeyResolver, PublicCovidPass, Verifier,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+const USAGE: &str = "\
+Verify NZ COVID Pass barcodes over HTTP.
+
+USAGE:
+    nzcp-server [OPTIONS]
+
+ENDPOINTS:
+    POST /verify    Verify the pass in the JSON body `{\"uri\": \"NZCP:/1/...\"}`
+    GET  /health    Whether the service is running
+    GET  /ready     Whether the trusted issuers' DID documents can be resolved
+
+OPTIONS:
+    --listen <ADDR>              Listen on the address (default: 127.0.0.1:8080)
+    --issuer <DID>               Trust only this issuer (repeatable, default: did:web:nzcp.identity.health.nz)
+    --did-proxy <URL>            Fetch the DID document for `did:web:<domain>` from `<URL>/<domain>/.well-known/did.json`
+                                 rather than the issuer's domain, e.g. from a local stand-in DID server
+    -h, --help                   Print this help
+";
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+const DEFAULT_ISSUER: &str = "did:web:nzcp.identity.health.nz";
+
+/// Barcode URIs are at most a few hundred bytes, so anything much larger isn't worth reading.
+const MAX_BODY_SIZE: usize = 16 * 1024;
+
+/// How long resolved keys are cached, and how long key IDs missing from DID documents are remembered.
+const KEY_TTL: Duration = Duration::from_secs(60 * 60);
+const NEGATIVE_KEY_TTL: Duration = Duration::from_secs(60);
+
+/// How long readiness probes wait for each DID document, so a slow issuer fails the probe rather than hanging it.
+const READY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long verifying a pass can take, including resolving the issuer's key, before failing as a network error.
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    listen: SocketAddr,
+    issuers: Vec<String>,
+    did_proxy: Option<String>,
+}
+
+struct State {
+    verifier: Verifier<KeyCache<Box<dyn KeyResolver>>>,
+    issuers: Vec<String>,
+    did_proxy: Option<DidWebProxyResolver>,
+    /// Shared by readiness probes and the DID proxy, so each reuses its connections.
+    http_client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct VerifyRequest {
+    uri: String,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let server = match Server::try_bind(&args.listen) {
+        Ok(server) => server,
+        Err(error) => {
+            eprintln!("error: could not listen on {}: {}", args.listen, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let state = Arc::new(State::new(args));
+    let server = server.serve(make_service_fn(move |_| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |request| handle(state.clone(), request))) }
+    }));
+
+    eprintln!("listening on http://{}", server.local_addr());
+    if let Err(error) = server.await {
+        eprintln!("error: {}", error);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Parse the arguments (excluding the program name), returning `None` if help was requested.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut listen = DEFAULT_LISTEN.parse().expect("the default address is valid");
+    let mut issuers = Vec::new();
+    let mut did_proxy = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |option: &str| args.next().ok_or_else(|| format!("missing value for '{}'", option));
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--listen" => {
+                let address = value(&arg)?;
+                listen = address
+                    .parse()
+                    .map_err(|_| format!("invalid address '{}', expected e.g. {}", address, DEFAULT_LISTEN))?;
+            }
+            "--issuer" => {
+                let issuer = value(&arg)?;
+                if DecentralizedIdentifier::parse(&issuer).is_none() {
+                    return Err(format!("invalid issuer '{}', expected a did:web DID", issuer));
+                }
+                issuers.push(issuer);
+            }
+            "--did-proxy" => did_proxy = Some(value(&arg)?),
+            option => return Err(format!("unknown option '{}'", option)),
+        }
+    }
+
+    if issuers.is_empty() {
+        issuers.push(String::from(DEFAULT_ISSUER));
+    }
+
+    Ok(Some(Args {
+        listen,
+        issuers,
+        did_proxy,
+    }))
+}
+
+impl State {
+    fn new(args: Args) -> Self {
+        let http_client = reqwest::Client::builder()
+            .connect_timeout(READY_TIMEOUT)
+            .timeout(READY_TIMEOUT)
+            .build()
+            .expect("the HTTP client has a valid configuration");
+        let did_proxy = args
+            .did_proxy
+            .map(|url| DidWebProxyResolver::new(url).with_client(http_client.clone()));
+        let resolver: Box<dyn KeyResolver> = match &did_proxy {
+            Some(did_proxy) => Box::new(did_proxy.clone()),
+            None => Box::new(DidWebResolver),
+        };
+
+        let verifier = Verifier::builder()
+            .trusted_issuers(
+                args.issuers
+                    .iter()
+                    .filter_map(|issuer| DecentralizedIdentifier::parse(issuer)),
+            )
+            .resolver(resolver)
+            .key_cache(KEY_TTL, NEGATIVE_KEY_TTL)
+            .build();
+
+        State {
+            verifier,
+            issuers: args.issuers,
+            did_proxy,
+            http_client,
+        }
+    }
+
+    fn document_url(&self, issuer: DecentralizedIdentifier<'_>) -> String {
+        match &self.did_proxy {
+            Some(did_proxy) => did_proxy.document_url(issuer),
+            None => issuer.document_url(),
+        }
+    }
+}
+
+async fn handle(state: Arc<State>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::POST, "/verify") => verify(&state, request.into_body()).await,
+        (&Method::GET, "/health") => json_response(StatusCode::OK, json!({ "status": "ok" })),
+        (&Method::GET, "/ready") => ready(&state).await,
+        (_, "/verify" | "/health" | "/ready") => error_response(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"),
+        _ => error_response(StatusCode::NOT_FOUND, "not found"),
+    };
+
+    Ok(response)
+}
+
+async fn verify(state: &State, body: Body) -> Response<Body> {
+    let body = match read_body(body).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let request: VerifyRequest = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(error) => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                &format!("expected a JSON body {{\"uri\": \"NZCP:/1/...\"}}: {}", error),
+            )
+        }
+    };
+
+    let verified = tokio::time::timeout(
+        VERIFY_TIMEOUT,
+        state.verifier.verify::<PublicCovidPass>(request.uri.trim()),
+    )
+    .await
+    .unwrap_or_else(|_| {
+        // only resolving the issuer's key can take this long
+        let error = DecentralizedIdentifierError::ResolutionError(format!(
+            "timed out after {} seconds",
+            VERIFY_TIMEOUT.as_secs()
+        ));
+        Err(NzcpError::from(CoseVerificationError::from(error)))
+    });
+
+    match verified {
+        Ok(verified) => json_response(
+            StatusCode::OK,
+            json!({
+                "valid": true,
+                "jti": verified.jti(),
+                "issuer": verified.issuer,
+                "kid": verified.kid,
+                "notBefore": verified.not_before.to_rfc3339(),
+                "expiry": verified.expiry.to_rfc3339(),
+                "version": verified.version,
+                "credentialSubject": verified.pass,
+            }),
+        ),
+        Err(error) => {
+            // the pass may well be valid, so don't give a verdict which could be cached
+            let status = if error.category() == ErrorCategory::Network {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            else {
+                StatusCode::OK
+            };
+
+            json_response(
+                status,
+                json!({
+                    "valid": false,
+                    "code": error.code(),
+                    "category": error.category(),
+                    "error": error.to_string(),
+                }),
+            )
+        }
+    }
+}
+
+/// Ready when every trusted issuer's DID document can be fetched, as passes can't be verified otherwise.
+async fn ready(state: &State) -> Response<Body> {
+    let mut errors = serde_json::Map::new();
+    for issuer in &state.issuers {
+        let did = DecentralizedIdentifier::parse(issuer).expect("issuers are validated when parsing arguments");
+        let result = match state.http_client.get(state.document_url(did)).send().await {
+            Ok(response) => response.error_for_status().map(|_| ()),
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            errors.insert(issuer.clone(), error.to_string().into());
+        }
+    }
+
+    if errors.is_empty() {
+        json_response(StatusCode::OK, json!({ "ready": true }))
+    }
+    else {
+        json_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({ "ready": false, "errors": errors }),
+        )
+    }
+}
+
+async fn read_body(mut body: Body) -> Result<Vec<u8>, Response<Body>> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|error| error_response(StatusCode::BAD_REQUEST, &error.to_string()))?;
+        if bytes.len() + chunk.len() > MAX_BODY_SIZE {
+            return Err(error_response(StatusCode::PAYLOAD_TOO_LARGE, "request body too large"));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes)
+}
+
+fn error_response(status: StatusCode, error: &str) -> Response<Body> {
+    json_response(status, json!({ "error": error }))
+}
+
+fn json_response(status: StatusCode, value: serde_json::Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(value.to_string()))
+        .expect("responses are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // https://nzcp.covid19.health.nz/#valid-worked-example
+    const BARCODE: &str = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX";
+    // https://nzcp.covid19.health.nz/#expired-pass
+    const EXPIRED_BARCODE: &str = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUX5AM2FQIGTBPBPYWYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVA56TNJCCUN2NVK5NGAYOZ6VIWACYIBM3QXW7SLCMD2WTJ3GSEI5JH7RXAEURGATOHAHXC2O6BEJKBSVI25ICTBR5SFYUDSVLB2F6SJ63LWJ6Z3FWNHOXF6A2QLJNUFRQNTRU";
+
+    /// Serve the spec's example DID document like a did:web proxy, returning the proxy URL.
+    async fn did_server() -> String {
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|request: Request<Body>| async move {
+                let response = match request.uri().path() {
+                    "/nzcp.covid19.health.nz/.well-known/did.json" => Response::new(Body::from(include_str!(
+                        "../../../tests/did/nzcp.covid19.health.nz.json"
+                    ))),
+                    _ => Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(Body::empty())
+                        .unwrap(),
+                };
+                Ok::<_, Infallible>(response)
+            }))
+        }));
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        url
+    }
+
+    /// Start the service trusting the issuers, returning its URL.
+    async fn service(issuers: &[&str]) -> String {
+        let state = Arc::new(State::new(Args {
+            listen: SocketAddr::from(([127, 0, 0, 1], 0)),
+            issuers: issuers.iter().map(|issuer| issuer.to_string()).collect(),
+            did_proxy: Some(did_server().await),
+        }));
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service_fn(move |_| {
+            let state = state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |request| handle(state.clone(), request))) }
+        }));
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        url
+    }
+
+    async fn post_verify(url: &str, body: impl Into<reqwest::Body>) -> (StatusCode, serde_json::Value) {
+        let response = reqwest::Client::new()
+            .post(format!("{}/verify", url))
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        (response.status(), response.json().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn verify_passes() {
+        let url = service(&["did:web:nzcp.covid19.health.nz"]).await;
+
+        let (status, body) = post_verify(&url, json!({ "uri": BARCODE }).to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["valid"], true);
+        assert_eq!(body["jti"], "urn:uuid:60a4f54d-4e30-4332-be33-ad78b1eafa4b");
+        assert_eq!(body["credentialSubject"]["givenName"], "Jack");
+
+        let (status, body) = post_verify(&url, json!({ "uri": EXPIRED_BARCODE }).to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["valid"], false);
+        assert_eq!(body["code"], "NZCP-EXPIRED");
+
+        let (status, _) = post_verify(&url, "NZCP:/1/...").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn untrusted_issuer() {
+        let url = service(&[DEFAULT_ISSUER]).await;
+
+        let (status, body) = post_verify(&url, json!({ "uri": BARCODE }).to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["code"], "NZCP-UNTRUSTED-ISSUER");
+    }
+
+    #[tokio::test]
+    async fn health_and_readiness() {
+        let url = service(&["did:web:nzcp.covid19.health.nz"]).await;
+        let get = |path: &str| reqwest::get(format!("{}{}", url, path));
+
+        assert_eq!(get("/health").await.unwrap().status(), StatusCode::OK);
+        assert_eq!(get("/ready").await.unwrap().status(), StatusCode::OK);
+        assert_eq!(get("/verify").await.unwrap().status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        // the stand-in DID server doesn't have the MoH DID document
+        let url = service(&[DEFAULT_ISSUER]).await;
+        let response = reqwest::get(format!("{}/ready", url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[test]
+    fn parse_server_args() {
+        let args = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+
+        assert_eq!(
+            args(&["--listen", "0.0.0.0:80", "--did-proxy", "http://localhost:8000"]),
+            Ok(Some(Args {
+                listen: SocketAddr::from(([0, 0, 0, 0], 80)),
+                issuers: vec![String::from(DEFAULT_ISSUER)],
+                did_proxy: Some(String::from("http://localhost:8000")),
+            }))
+        );
+        assert!(args(&["--issuer", "nzcp.covid19.health.nz"]).is_err());
+        assert!(args(&["--listen", "localhost"]).is_err());
+    }
+}
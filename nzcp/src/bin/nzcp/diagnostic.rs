@@ -0,0 +1,303 @@
+use std::fmt::Write;
+
+use thiserror::Error;
+
+/// Nesting deeper than this is rejected rather than risking a stack overflow on a malicious barcode.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DiagnosticError {
+    #[error("unexpected end of CBOR data at byte {0}")]
+    UnexpectedEnd(usize),
+    #[error("invalid CBOR initial byte {0:#04x} at byte {1}")]
+    InvalidInitialByte(u8, usize),
+    #[error("invalid UTF-8 in text string at byte {0}")]
+    InvalidUtf8(usize),
+    #[error("CBOR nested too deeply at byte {0}")]
+    TooDeep(usize),
+    #[error("{0} trailing bytes after the CBOR data item")]
+    TrailingBytes(usize),
+}
+
+/// Format a single CBOR data item in diagnostic notation (RFC 8949 section 8), keeping map entries in encoded order.
+pub fn diagnostic(bytes: &[u8]) -> Result<String, DiagnosticError> {
+    let mut decoder = Decoder { bytes, offset: 0 };
+    let mut out = String::new();
+
+    decoder.item(&mut out, 0)?;
+
+    match bytes.len() - decoder.offset {
+        0 => Ok(out),
+        trailing => Err(DiagnosticError::TrailingBytes(trailing)),
+    }
+}
+
+/// Format bytes as lowercase hex.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+/// The additional information value for an indefinite length.
+const INDEFINITE: u8 = 31;
+const BREAK: u8 = 0xff;
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DiagnosticError> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(DiagnosticError::UnexpectedEnd(self.bytes.len()))?;
+        let taken = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(taken)
+    }
+
+    fn peek(&self) -> Result<u8, DiagnosticError> {
+        self.bytes
+            .get(self.offset)
+            .copied()
+            .ok_or(DiagnosticError::UnexpectedEnd(self.offset))
+    }
+
+    fn argument(&mut self, initial: u8, start: usize) -> Result<u64, DiagnosticError> {
+        let size = match initial & 0x1f {
+            info @ 0..=23 => return Ok(info.into()),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err(DiagnosticError::InvalidInitialByte(initial, start)),
+        };
+
+        Ok(self
+            .take(size)?
+            .iter()
+            .fold(0, |value, byte| value << 8 | u64::from(*byte)))
+    }
+
+    fn length(&mut self, initial: u8, start: usize) -> Result<usize, DiagnosticError> {
+        let length = self.argument(initial, start)?;
+        usize::try_from(length).map_err(|_| DiagnosticError::UnexpectedEnd(self.bytes.len()))
+    }
+
+    /// Whether the next byte is a break, consuming it if so.
+    fn at_break(&mut self) -> Result<bool, DiagnosticError> {
+        let at_break = self.peek()? == BREAK;
+        if at_break {
+            self.offset += 1;
+        }
+        Ok(at_break)
+    }
+
+    fn item(&mut self, out: &mut String, depth: usize) -> Result<(), DiagnosticError> {
+        let start = self.offset;
+        if depth > MAX_DEPTH {
+            return Err(DiagnosticError::TooDeep(start));
+        }
+
+        let initial = self.take(1)?[0];
+        let indefinite = initial & 0x1f == INDEFINITE;
+
+        match initial >> 5 {
+            0 => write_display(out, self.argument(initial, start)?),
+            1 => write_display(out, -1 - i128::from(self.argument(initial, start)?)),
+            2 | 3 if indefinite => {
+                out.push_str("(_ ");
+                let mut first = true;
+                while !self.at_break()? {
+                    if !first {
+                        out.push_str(", ");
+                    }
+                    first = false;
+                    // chunks must be definite strings of the same major type
+                    let chunk = self.peek()?;
+                    if chunk >> 5 != initial >> 5 || chunk & 0x1f == INDEFINITE {
+                        return Err(DiagnosticError::InvalidInitialByte(chunk, self.offset));
+                    }
+                    self.item(out, depth + 1)?;
+                }
+                out.push(')');
+            }
+            2 => {
+                let len = self.length(initial, start)?;
+                let bytes = self.take(len)?;
+                write_display(out, format_args!("h'{}'", hex(bytes)));
+            }
+            3 => {
+                let len = self.length(initial, start)?;
+                let text = std::str::from_utf8(self.take(len)?).map_err(|_| DiagnosticError::InvalidUtf8(start))?;
+                write_display(out, format_args!("{:?}", text));
+            }
+            4 => {
+                out.push('[');
+                if indefinite {
+                    out.push_str("_ ");
+                    let mut first = true;
+                    while !self.at_break()? {
+                        if !first {
+                            out.push_str(", ");
+                        }
+                        first = false;
+                        self.item(out, depth + 1)?;
+                    }
+                }
+                else {
+                    for index in 0..self.length(initial, start)? {
+                        if index > 0 {
+                            out.push_str(", ");
+                        }
+                        self.item(out, depth + 1)?;
+                    }
+                }
+                out.push(']');
+            }
+            5 => {
+                out.push('{');
+                if indefinite {
+                    out.push_str("_ ");
+                    let mut first = true;
+                    while !self.at_break()? {
+                        if !first {
+                            out.push_str(", ");
+                        }
+                        first = false;
+                        self.entry(out, depth)?;
+                    }
+                }
+                else {
+                    for index in 0..self.length(initial, start)? {
+                        if index > 0 {
+                            out.push_str(", ");
+                        }
+                        self.entry(out, depth)?;
+                    }
+                }
+                out.push('}');
+            }
+            6 => {
+                write_display(out, format_args!("{}(", self.argument(initial, start)?));
+                self.item(out, depth + 1)?;
+                out.push(')');
+            }
+            _ => self.simple(out, initial, start)?,
+        }
+
+        Ok(())
+    }
+
+    fn entry(&mut self, out: &mut String, depth: usize) -> Result<(), DiagnosticError> {
+        self.item(out, depth + 1)?;
+        out.push_str(": ");
+        self.item(out, depth + 1)
+    }
+
+    fn simple(&mut self, out: &mut String, initial: u8, start: usize) -> Result<(), DiagnosticError> {
+        match initial & 0x1f {
+            20 => out.push_str("false"),
+            21 => out.push_str("true"),
+            22 => out.push_str("null"),
+            23 => out.push_str("undefined"),
+            value @ 0..=19 => write_display(out, format_args!("simple({})", value)),
+            24 => write_display(out, format_args!("simple({})", self.take(1)?[0])),
+            25 => {
+                let half = self.argument(initial, start)? as u16;
+                write_float(out, f16_to_f64(half));
+            }
+            26 => write_float(out, f32::from_bits(self.argument(initial, start)? as u32).into()),
+            27 => write_float(out, f64::from_bits(self.argument(initial, start)?)),
+            _ => return Err(DiagnosticError::InvalidInitialByte(initial, start)),
+        }
+
+        Ok(())
+    }
+}
+
+fn write_display(out: &mut String, value: impl std::fmt::Display) {
+    let _ = write!(out, "{}", value);
+}
+
+fn write_float(out: &mut String, value: f64) {
+    if value.is_nan() {
+        out.push_str("NaN");
+    }
+    else if value.is_infinite() {
+        out.push_str(if value > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+    else if value.fract() == 0.0 {
+        write_display(out, format_args!("{:.1}", value));
+    }
+    else {
+        write_display(out, value);
+    }
+}
+
+fn f16_to_f64(half: u16) -> f64 {
+    let exponent = i32::from((half >> 10) & 0x1f);
+    let mantissa = f64::from(half & 0x3ff);
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1024.0 + mantissa) * 2f64.powi(exponent - 25),
+    };
+
+    if half & 0x8000 == 0 {
+        magnitude
+    }
+    else {
+        -magnitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diag(hex: &str) -> Result<String, DiagnosticError> {
+        let bytes: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).unwrap())
+            .collect();
+        diagnostic(&bytes)
+    }
+
+    #[test]
+    fn protected_headers() {
+        // https://nzcp.covid19.health.nz/#valid-worked-example
+        assert_eq!(diag("a204456b65792d310126").unwrap(), r#"{4: h'6b65792d31', 1: -7}"#);
+    }
+
+    #[test]
+    fn rfc_8949_examples() {
+        assert_eq!(diag("1b000000e8d4a51000").unwrap(), "1000000000000");
+        assert_eq!(diag("3903e7").unwrap(), "-1000");
+        assert_eq!(diag("f93e00").unwrap(), "1.5");
+        assert_eq!(diag("f97c00").unwrap(), "Infinity");
+        assert_eq!(diag("fb3ff199999999999a").unwrap(), "1.1");
+        assert_eq!(diag("d818456449455446").unwrap(), "24(h'6449455446')");
+        assert_eq!(diag("6449455446").unwrap(), r#""IETF""#);
+        assert_eq!(diag("9f018202039f0405ffff").unwrap(), "[_ 1, [2, 3], [_ 4, 5]]");
+        assert_eq!(diag("bf61610161629f0203ffff").unwrap(), r#"{_ "a": 1, "b": [_ 2, 3]}"#);
+        assert_eq!(diag("5f42010243030405ff").unwrap(), "(_ h'0102', h'030405')");
+        assert_eq!(diag("f4f5f6f7f0").unwrap_err(), DiagnosticError::TrailingBytes(4));
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(diag("a2").unwrap_err(), DiagnosticError::UnexpectedEnd(1));
+        assert_eq!(diag("5a00000010").unwrap_err(), DiagnosticError::UnexpectedEnd(5));
+        assert_eq!(diag("1c").unwrap_err(), DiagnosticError::InvalidInitialByte(0x1c, 0));
+        assert_eq!(diag(&"81".repeat(100)).unwrap_err(), DiagnosticError::TooDeep(65));
+    }
+}
@@ -0,0 +1,433 @@
+//! The `nzcp` command line tool, for verifying, decoding and inspecting pass barcodes.
+
+use std::{
+    env,
+    error::Error,
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use nzcp::{
+    decode_pass_uri, error::NzcpError, payload::barcode::QrBarcode, Credential, DecentralizedIdentifier,
+    PublicCovidPass, RawPass, TrustStore, Verifier,
+};
+use serde_json::json;
+
+use self::diagnostic::{diagnostic, hex};
+
+mod diagnostic;
+
+const USAGE: &str = "\
+Verify, decode and inspect NZ COVID Pass barcodes.
+
+USAGE:
+    nzcp <COMMAND> [OPTIONS] [URI]
+
+The URI (`NZCP:/1/...`) is read from the argument, from the file given with `--file`, or from stdin if neither is
+given (or the argument is `-`).
+
+COMMANDS:
+    verify     Verify the pass, printing the result as JSON (exits with 0 if valid, 1 if not)
+    decode     Print the protected headers, CWT claims and verifiable credential as JSON, without verification
+    inspect    Print each COSE segment as hex and CBOR diagnostic notation
+
+OPTIONS:
+    -f, --file <FILE>            Read the URI from the file
+    -h, --help                   Print this help
+
+VERIFY OPTIONS:
+    --issuer <DID>               Trust only this issuer (repeatable, default: did:web:nzcp.identity.health.nz)
+    --did-document <FILE>        Resolve keys from the DID document in the file (repeatable, the MoH document is
+                                 bundled). This does not trust its issuer, see `--issuer`
+    --offline                    Never resolve DID documents online
+    --report                     Make every check and print a report, rather than stopping at the first failure
+    --raw                        Accept any pass type, printing the credential subject as is
+";
+
+/// Exit code for invalid passes or barcodes.
+const EXIT_INVALID: u8 = 1;
+/// Exit code for invalid command line usage.
+const EXIT_USAGE: u8 = 2;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Verify,
+    Decode,
+    Inspect,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Input {
+    Argument(String),
+    File(PathBuf),
+    Stdin,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    command: Command,
+    input: Input,
+    issuers: Vec<String>,
+    did_documents: Vec<PathBuf>,
+    offline: bool,
+    report: bool,
+    raw: bool,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let uri = match read_uri(&args.input) {
+        Ok(uri) => uri,
+        Err(error) => {
+            eprintln!("error: could not read the URI: {}", error);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let result = match args.command {
+        Command::Verify => return verify(&uri, &args).await,
+        Command::Decode => decode(&uri),
+        Command::Inspect => inspect(&uri),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::from(EXIT_INVALID)
+        }
+    }
+}
+
+/// Parse the arguments (excluding the program name), returning `None` if help was requested.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let command = match args.next().as_deref() {
+        Some("verify") => Command::Verify,
+        Some("decode") => Command::Decode,
+        Some("inspect") => Command::Inspect,
+        Some("-h" | "--help") => return Ok(None),
+        Some(command) => return Err(format!("unknown command '{}'", command)),
+        None => return Err(String::from("missing command")),
+    };
+
+    let mut input = None;
+    let mut issuers = Vec::new();
+    let mut did_documents = Vec::new();
+    let mut offline = false;
+    let mut report = false;
+    let mut raw = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = |option: &str| args.next().ok_or_else(|| format!("missing value for '{}'", option));
+
+        let next_input = match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-f" | "--file" => Input::File(value(&arg)?.into()),
+            "--issuer" if command == Command::Verify => {
+                let issuer = value(&arg)?;
+                if DecentralizedIdentifier::parse(&issuer).is_none() {
+                    return Err(format!("invalid issuer '{}', expected a did:web DID", issuer));
+                }
+                issuers.push(issuer);
+                continue;
+            }
+            "--did-document" if command == Command::Verify => {
+                did_documents.push(value(&arg)?.into());
+                continue;
+            }
+            "--offline" if command == Command::Verify => {
+                offline = true;
+                continue;
+            }
+            "--report" if command == Command::Verify => {
+                report = true;
+                continue;
+            }
+            "--raw" if command == Command::Verify => {
+                raw = true;
+                continue;
+            }
+            "-" => Input::Stdin,
+            option if option.starts_with('-') => return Err(format!("unknown option '{}'", option)),
+            _ => Input::Argument(arg),
+        };
+
+        if input.replace(next_input).is_some() {
+            return Err(String::from("only one URI can be given"));
+        }
+    }
+
+    Ok(Some(Args {
+        command,
+        input: input.unwrap_or(Input::Stdin),
+        issuers,
+        did_documents,
+        offline,
+        report,
+        raw,
+    }))
+}
+
+fn read_uri(input: &Input) -> io::Result<String> {
+    let uri = match input {
+        Input::Argument(uri) => uri.clone(),
+        Input::File(path) => fs::read_to_string(path)?,
+        Input::Stdin => {
+            let mut uri = String::new();
+            io::stdin().read_to_string(&mut uri)?;
+            uri
+        }
+    };
+
+    Ok(uri.trim().to_owned())
+}
+
+async fn verify(uri: &str, args: &Args) -> ExitCode {
+    let mut trust_store = TrustStore::ministry_of_health().with_online_fallback(!args.offline);
+    for path in &args.did_documents {
+        if let Err(error) = trust_store.add_document_file(path) {
+            eprintln!("error: invalid DID document '{}': {}", path.display(), error);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    }
+
+    let mut builder = Verifier::builder().resolver(trust_store);
+    if !args.issuers.is_empty() {
+        builder = builder.trusted_issuers(
+            args.issuers
+                .iter()
+                .filter_map(|did| DecentralizedIdentifier::parse(did)),
+        );
+    }
+
+    let verifier = builder.build();
+
+    if args.raw {
+        // the declared type and contexts along with the subject
+        verify_as(&verifier, uri, args.report, |pass: &RawPass| json!(pass)).await
+    }
+    else {
+        verify_as(
+            &verifier,
+            uri,
+            args.report,
+            |pass: &PublicCovidPass| json!({ "credentialSubject": pass }),
+        )
+        .await
+    }
+}
+
+/// Verify the pass as `P`, printing the fields given by `credential_json` along with the verified claims.
+async fn verify_as<P: Credential>(
+    verifier: &Verifier<TrustStore>,
+    uri: &str,
+    report: bool,
+    credential_json: fn(&P) -> serde_json::Value,
+) -> ExitCode {
+    if report {
+        let report = verifier.report::<P>(uri).await;
+        print!("{}", report);
+        return if report.is_valid() {
+            ExitCode::SUCCESS
+        }
+        else {
+            ExitCode::from(EXIT_INVALID)
+        };
+    }
+
+    match verifier.verify::<P>(uri).await {
+        Ok(verified) => {
+            let mut output = json!({
+                "valid": true,
+                "jti": verified.jti(),
+                "issuer": verified.issuer,
+                "kid": verified.kid,
+                "notBefore": verified.not_before.to_rfc3339(),
+                "expiry": verified.expiry.to_rfc3339(),
+                "version": verified.version,
+            });
+            if let (Some(output), serde_json::Value::Object(credential)) =
+                (output.as_object_mut(), credential_json(&verified.pass))
+            {
+                output.extend(credential);
+            }
+            print_json(&output);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            print_json(&json!({
+                "valid": false,
+                "code": error.code(),
+                "category": error.category(),
+                "error": error.to_string(),
+            }));
+            ExitCode::from(EXIT_INVALID)
+        }
+    }
+}
+
+fn decode(uri: &str) -> Result<(), Box<dyn Error>> {
+    let pass = match decode_pass_uri::<RawPass>(uri) {
+        Ok(pass) => pass,
+        Err(NzcpError::InvalidPayload(error)) => {
+            return Err(format!("{} (use `nzcp inspect` to see the raw CBOR)", error).into())
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let protected_headers = pass.unverified_protected_headers();
+    let cwt_claims = pass.unverified_cwt_claims();
+    let verifiable_credential = cwt_claims.verifiable_credential();
+    let (vc_type, pass_type) = verifiable_credential.credential_type();
+
+    print_json(&json!({
+        "protectedHeaders": {
+            "kid": protected_headers.kid,
+            "alg": protected_headers.algorithm.to_string(),
+        },
+        "cwtClaims": {
+            "jti": cwt_claims.cwt_token_id().to_urn().to_string(),
+            "iss": cwt_claims.issuer().to_string(),
+            "nbf": cwt_claims.not_before().to_rfc3339(),
+            "exp": cwt_claims.expiry().to_rfc3339(),
+        },
+        "vc": {
+            "@context": verifiable_credential.context(),
+            "version": verifiable_credential.version(),
+            "type": [vc_type, pass_type],
+            "credentialSubject": serde_json::to_value(&verifiable_credential.credential_subject().credential_subject)?,
+        },
+    }));
+
+    Ok(())
+}
+
+fn inspect(uri: &str) -> Result<(), Box<dyn Error>> {
+    let barcode: QrBarcode = uri.parse()?;
+
+    println!("Barcode version {}", barcode.version);
+    println!("COSE structure ({} bytes)", barcode.cbor.len());
+    print_segment(&barcode.cbor);
+
+    // split the segments out of the `COSE_Sign1` array without any further validation
+    let segments = match serde_cbor::from_slice(&barcode.cbor)? {
+        serde_cbor::Value::Tag(_, value) => match *value {
+            serde_cbor::Value::Array(segments) => segments,
+            _ => return Err("the tagged CBOR value is not a COSE array".into()),
+        },
+        serde_cbor::Value::Array(segments) => segments,
+        _ => return Err("the CBOR value is not a COSE array".into()),
+    };
+
+    let names = [
+        "protected headers",
+        "unprotected headers",
+        "payload (CWT claims)",
+        "signature",
+    ];
+    for (index, segment) in segments.iter().enumerate() {
+        let name = names.get(index).copied().unwrap_or("unexpected segment");
+        println!();
+        match segment {
+            serde_cbor::Value::Bytes(bytes) if name == "signature" => {
+                println!("{} (byte string, {} bytes)", name, bytes.len());
+                println!("  hex:  {}", hex(bytes));
+            }
+            serde_cbor::Value::Bytes(bytes) => {
+                println!("{} (byte string, {} bytes)", name, bytes.len());
+                print_segment(bytes);
+            }
+            value => {
+                println!("{}", name);
+                print_segment(&serde_cbor::to_vec(value)?);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_segment(bytes: &[u8]) {
+    println!("  hex:  {}", hex(bytes));
+    match diagnostic(bytes) {
+        Ok(diagnostic) => println!("  diag: {}", diagnostic),
+        Err(error) => println!("  diag: invalid CBOR: {}", error),
+    }
+}
+
+fn print_json(value: &serde_json::Value) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).expect("JSON values always serialize")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_verify_args() {
+        assert_eq!(
+            args(&[
+                "verify",
+                "--issuer",
+                "did:web:nzcp.covid19.health.nz",
+                "--did-document",
+                "did.json",
+                "--offline",
+                "--report",
+                "--raw",
+                "NZCP:/1/ABC"
+            ]),
+            Ok(Some(Args {
+                command: Command::Verify,
+                input: Input::Argument(String::from("NZCP:/1/ABC")),
+                issuers: vec![String::from("did:web:nzcp.covid19.health.nz")],
+                did_documents: vec![PathBuf::from("did.json")],
+                offline: true,
+                report: true,
+                raw: true,
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_input_args() {
+        assert_eq!(args(&["decode"]).unwrap().unwrap().input, Input::Stdin);
+        assert_eq!(args(&["decode", "-"]).unwrap().unwrap().input, Input::Stdin);
+        assert_eq!(
+            args(&["inspect", "-f", "pass.txt"]).unwrap().unwrap().input,
+            Input::File(PathBuf::from("pass.txt"))
+        );
+        assert_eq!(args(&["inspect", "--help"]), Ok(None));
+    }
+
+    #[test]
+    fn reject_invalid_args() {
+        assert!(args(&[]).is_err());
+        assert!(args(&["check"]).is_err());
+        assert!(args(&["decode", "--offline"]).is_err());
+        assert!(args(&["verify", "--issuer", "nzcp.covid19.health.nz"]).is_err());
+        assert!(args(&["verify", "--issuer"]).is_err());
+        assert!(args(&["verify", "NZCP:/1/A", "NZCP:/1/B"]).is_err());
+    }
+}
@@ -0,0 +1,24 @@
+use chrono::{DateTime, Utc};
+
+/// The source of the current time when checking that a pass is active and has not expired.
+///
+/// A fixed `DateTime<Utc>` is also a clock, which can be used to check whether a pass was valid at that time.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock, used by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+impl Clock for DateTime<Utc> {
+    fn now(&self) -> DateTime<Utc> {
+        *self
+    }
+}
@@ -1,10 +1,9 @@
 use std::{fmt, str::FromStr};
 
 use did_web::DIDWeb;
-use p256::{ecdsa::VerifyingKey, elliptic_curve::generic_array::GenericArray, EncodedPoint};
 use serde::{
     de::{self, Visitor},
-    Deserialize, Deserializer,
+    Deserialize, Deserializer, Serialize, Serializer,
 };
 use ssi::{
     did::{Document, VerificationMethod, DIDURL},
@@ -13,9 +12,15 @@ use ssi::{
 };
 use thiserror::Error;
 
-const DID_WEB: &'static str = "did:web:";
+use crate::payload::cose::signature::verify::VerifyingKey;
 
-#[doc(hidden)]
+pub(crate) mod key_cache;
+pub(crate) mod resolver;
+pub(crate) mod trust_store;
+
+const DID_WEB: &str = "did:web:";
+
+/// A Decentralized Identifier (DID) of a pass issuer, e.g. `did:web:nzcp.identity.health.nz`.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum DecentralizedIdentifier<'a> {
     Web(&'a str),
@@ -41,12 +46,7 @@ impl<'de> Visitor<'de> for DecentralizedIdentifierVisitor {
     where
         E: de::Error,
     {
-        if let Some(did) = did.strip_prefix(DID_WEB) {
-            Ok(DecentralizedIdentifier::Web(did))
-        }
-        else {
-            Err(E::custom("invalid DID"))
-        }
+        DecentralizedIdentifier::parse(did).ok_or_else(|| E::custom("invalid DID"))
     }
 }
 
@@ -59,7 +59,39 @@ impl<'de: 'a, 'a> Deserialize<'de> for DecentralizedIdentifier<'a> {
     }
 }
 
-#[derive(Debug, Error, PartialEq, Eq)]
+impl<'a> Serialize for DecentralizedIdentifier<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.did())
+    }
+}
+
+/// An owned [`DecentralizedIdentifier`], to keep a DID which has already been parsed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) enum OwnedDecentralizedIdentifier {
+    Web(String),
+}
+
+impl OwnedDecentralizedIdentifier {
+    pub(crate) fn as_borrowed(&self) -> DecentralizedIdentifier<'_> {
+        match self {
+            OwnedDecentralizedIdentifier::Web(id) => DecentralizedIdentifier::Web(id),
+        }
+    }
+}
+
+impl<'a> From<DecentralizedIdentifier<'a>> for OwnedDecentralizedIdentifier {
+    fn from(did: DecentralizedIdentifier<'a>) -> Self {
+        match did {
+            DecentralizedIdentifier::Web(id) => OwnedDecentralizedIdentifier::Web(id.to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+#[non_exhaustive]
 pub enum DecentralizedIdentifierError {
     #[error("DID resolution error: {0}")]
     ResolutionError(String),
@@ -77,64 +109,58 @@ pub enum DecentralizedIdentifierError {
     NotJsonWebKey2020,
     #[error("verificationMethod was missing publicKeyJwk")]
     MissingJWK,
-    #[error("publicKeyJwk was not elliptic curve")]
+    #[error("publicKeyJwk was not an elliptic curve or octet key pair key")]
     JWKNotEllipticCurve,
     #[error("publicKeyJwk was missing x coordinate")]
     JWKMissingX,
     #[error("publicKeyJwk was missing y coordinate")]
     JWKMissingY,
-    #[error("publicKeyJwk 'crv' was not 'P-256'")]
+    #[error("publicKeyJwk 'crv' was not 'P-256', 'P-384', 'P-521' or 'Ed25519'")]
     JWKWrongCurve,
     #[error("publicKeyJwk was invalid")]
     InvalidJWK,
+    #[error("the DID document for '{0}' was missing from the trust store")]
+    MissingDocument(String),
 }
 
 impl<'a> DecentralizedIdentifier<'a> {
-    fn did(&self) -> String {
+    /// Parse a DID string, returning `None` if its DID method isn't `web`.
+    pub fn parse(did: &'a str) -> Option<Self> {
+        did.strip_prefix(DID_WEB).map(DecentralizedIdentifier::Web)
+    }
+
+    pub(crate) fn did(&self) -> String {
         match self {
             DecentralizedIdentifier::Web(did) => format!("{}{}", DID_WEB, did),
         }
     }
 
+    /// The URL the DID document is published at, e.g. `https://nzcp.identity.health.nz/.well-known/did.json`.
+    pub fn document_url(&self) -> String {
+        format!("https://{}", self.document_path())
+    }
+
+    /// The domain and path of the DID document, e.g. `nzcp.identity.health.nz/.well-known/did.json`.
+    pub(crate) fn document_path(&self) -> String {
+        let DecentralizedIdentifier::Web(id) = self;
+        let mut parts = id.split(':');
+        // a port is percent-encoded, e.g. `did:web:localhost%3A8080`, and percent-encoding is case-insensitive
+        let domain = parts.next().unwrap_or_default().replace("%3A", ":").replace("%3a", ":");
+        let path: Vec<_> = parts.collect();
+
+        if path.is_empty() {
+            format!("{}/.well-known/did.json", domain)
+        }
+        else {
+            format!("{}/{}/did.json", domain, path.join("/"))
+        }
+    }
+
     async fn resolve_document(&self) -> Result<Document, DecentralizedIdentifierError> {
-        // TODO: horrifically disgusting temporary work around for https://github.com/vaxxnz/nzcp-rust/issues/1
         let (metadata, doc_data, _) = DIDWeb
             .resolve_representation(&self.did(), &ResolutionInputMetadata::default())
             .await;
-        let doc_opt: Option<serde_json::Value> = if doc_data.is_empty() {
-            None
-        }
-        else {
-            match serde_json::from_slice(&doc_data) {
-                Ok(doc) => doc,
-                Err(err) => return Err(DecentralizedIdentifierError::ResolutionError(err.to_string())),
-            }
-        };
-
-        let document = doc_opt
-            .and_then(|mut doc_opt| {
-                if let Some(id) = doc_opt.get_mut("@context") {
-                    match id {
-                        serde_json::Value::String(id) if id == "https://w3.org/ns/did/v1" => {
-                            *id = String::from("https://www.w3.org/ns/did/v1");
-                        }
-                        serde_json::Value::Array(arr)
-                            if arr.first().and_then(|v| v.as_str()) == Some("https://w3.org/ns/did/v1") =>
-                        {
-                            *arr.first_mut().unwrap() =
-                                serde_json::Value::String(String::from("https://www.w3.org/ns/did/v1"));
-                        }
-                        _ => (),
-                    }
-                }
-
-                Some(doc_opt)
-            })
-            .map(|doc| {
-                serde_json::from_value(doc)
-                    .map_err(|err| DecentralizedIdentifierError::ResolutionError(err.to_string()))
-            })
-            .transpose()?;
+        let document = parse_document(&doc_data)?;
 
         // let (metadata, document, _) = DIDWeb.resolve(&self.did(), &ResolutionInputMetadata::default()).await;
 
@@ -152,22 +178,32 @@ impl<'a> DecentralizedIdentifier<'a> {
     pub async fn resolve_verifying_key(&self, kid: &str) -> Result<VerifyingKey, DecentralizedIdentifierError> {
         let document = self.resolve_document().await?;
 
+        self.verifying_key_from_document(&document, kid)
+    }
+
+    /// Get the verifying key for the key ID from the issuer's DID document.
+    pub(crate) fn verifying_key_from_document(
+        &self,
+        document: &Document,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
         let absolute_key = format!("{}#{}", self.did(), kid);
         let absolute_key_url = DIDURL::from_str(&absolute_key).expect("invalid iss/kid DID");
 
 
         use DecentralizedIdentifierError::*;
-        let assertion_methods = document.assertion_method.ok_or(MissingAssertionMethods)?;
+        let assertion_methods = document.assertion_method.as_ref().ok_or(MissingAssertionMethods)?;
         if !assertion_methods.contains(&VerificationMethod::DIDURL(absolute_key_url)) {
             return Err(MissingAssertionMethod(absolute_key));
         }
 
         let verification_method = document
             .verification_method
+            .as_ref()
             .ok_or(MissingVerificationMethods)?
-            .into_iter()
+            .iter()
             .find_map(|method| match method {
-                VerificationMethod::Map(map) => (&map.id == &absolute_key).then(|| map),
+                VerificationMethod::Map(map) => (map.id == absolute_key).then_some(map),
                 _ => None,
             })
             .ok_or(MissingVerificationMethod(absolute_key))?;
@@ -175,30 +211,220 @@ impl<'a> DecentralizedIdentifier<'a> {
         if verification_method.type_ != "JsonWebKey2020" {
             Err(NotJsonWebKey2020)
         }
-        else if let Some(jwk) = verification_method.public_key_jwk {
-            let ec = match jwk.params {
-                jwk::Params::EC(ec) => ec,
-                _ => return Err(JWKNotEllipticCurve),
-            };
-
-            if ec.curve.as_deref() != Some("P-256") {
-                return Err(JWKWrongCurve);
-            }
+        else if let Some(jwk) = &verification_method.public_key_jwk {
+            verifying_key_from_jwk(&jwk.params)
+        }
+        else {
+            Err(MissingJWK)
+        }
+    }
+}
 
-            let x = ec.x_coordinate.ok_or(JWKMissingX)?;
-            let y = ec.y_coordinate.ok_or(JWKMissingY)?;
+/// Get the verifying key from a JWK, for the algorithm its curve is used with: P-256, P-384 and P-521 keys for ES256,
+/// ES384 and ES512, and Ed25519 keys for EdDSA.
+fn verifying_key_from_jwk(params: &jwk::Params) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+    use DecentralizedIdentifierError::*;
 
-            let point = EncodedPoint::from_affine_coordinates(
-                &GenericArray::from_slice(&x.0),
-                &GenericArray::from_slice(&y.0),
-                false,
-            );
-            let verifying_key = VerifyingKey::from_encoded_point(&point).map_err(|_| InvalidJWK)?;
+    match params {
+        jwk::Params::EC(ec) => match ec.curve.as_deref() {
+            Some("P-256") => p256::ecdsa::VerifyingKey::from_sec1_bytes(&sec1_point(ec, 32)?)
+                .map(VerifyingKey::from)
+                .map_err(|_| InvalidJWK),
+            Some("P-384") => p384::ecdsa::VerifyingKey::from_sec1_bytes(&sec1_point(ec, 48)?)
+                .map(VerifyingKey::from)
+                .map_err(|_| InvalidJWK),
+            Some("P-521") => p521::ecdsa::VerifyingKey::from_sec1_bytes(&sec1_point(ec, 66)?)
+                .map(VerifyingKey::from)
+                .map_err(|_| InvalidJWK),
+            _ => Err(JWKWrongCurve),
+        },
+        jwk::Params::OKP(okp) if okp.curve == "Ed25519" => {
+            let bytes = <[u8; 32]>::try_from(okp.public_key.0.as_slice()).map_err(|_| InvalidJWK)?;
+            let verifying_key = ed25519_dalek::VerifyingKey::from_bytes(&bytes).map_err(|_| InvalidJWK)?;
 
-            Ok(verifying_key)
+            Ok(verifying_key.into())
         }
-        else {
-            Err(MissingJWK)
+        jwk::Params::OKP(_) => Err(JWKWrongCurve),
+        _ => Err(JWKNotEllipticCurve),
+    }
+}
+
+/// The uncompressed SEC1 encoding of an EC JWK's point, whose coordinates must be the curve's field size.
+fn sec1_point(ec: &jwk::ECParams, coordinate_length: usize) -> Result<Vec<u8>, DecentralizedIdentifierError> {
+    use DecentralizedIdentifierError::*;
+
+    let x = ec.x_coordinate.as_ref().ok_or(JWKMissingX)?;
+    let y = ec.y_coordinate.as_ref().ok_or(JWKMissingY)?;
+    if x.0.len() != coordinate_length || y.0.len() != coordinate_length {
+        return Err(InvalidJWK);
+    }
+
+    Ok([&[0x04][..], &x.0, &y.0].concat())
+}
+
+/// Fetch and parse the DID document at the URL.
+#[cfg(feature = "proxy")]
+pub(crate) async fn fetch_document(
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<Document, DecentralizedIdentifierError> {
+    let resolution_error = |error: reqwest::Error| DecentralizedIdentifierError::ResolutionError(error.to_string());
+    let doc_data = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(resolution_error)?
+        .bytes()
+        .await
+        .map_err(resolution_error)?;
+
+    parse_document(&doc_data)?.ok_or(DecentralizedIdentifierError::EmptyDocument)
+}
+
+/// Parse a DID document, returning `None` if it is empty.
+pub(crate) fn parse_document(doc_data: &[u8]) -> Result<Option<Document>, DecentralizedIdentifierError> {
+    // TODO: horrifically disgusting temporary work around for https://github.com/vaxxnz/nzcp-rust/issues/1
+    let doc_opt: Option<serde_json::Value> = if doc_data.is_empty() {
+        None
+    }
+    else {
+        match serde_json::from_slice(doc_data) {
+            Ok(doc) => doc,
+            Err(err) => return Err(DecentralizedIdentifierError::ResolutionError(err.to_string())),
         }
+    };
+
+    doc_opt
+        .map(|mut doc_opt| {
+            if let Some(id) = doc_opt.get_mut("@context") {
+                match id {
+                    serde_json::Value::String(id) if id == "https://w3.org/ns/did/v1" => {
+                        *id = String::from("https://www.w3.org/ns/did/v1");
+                    }
+                    serde_json::Value::Array(arr)
+                        if arr.first().and_then(|v| v.as_str()) == Some("https://w3.org/ns/did/v1") =>
+                    {
+                        *arr.first_mut().unwrap() =
+                            serde_json::Value::String(String::from("https://www.w3.org/ns/did/v1"));
+                    }
+                    _ => (),
+                }
+            }
+
+            doc_opt
+        })
+        .map(|doc| {
+            serde_json::from_value(doc).map_err(|err| DecentralizedIdentifierError::ResolutionError(err.to_string()))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use ssi::jwk::{Base64urlUInt, ECParams, OctetParams, Params};
+
+    use super::*;
+    use crate::SignatureAlgorithm;
+
+    #[test]
+    fn document_urls() {
+        assert_eq!(
+            DecentralizedIdentifier::Web("nzcp.identity.health.nz").document_url(),
+            "https://nzcp.identity.health.nz/.well-known/did.json"
+        );
+        assert_eq!(
+            DecentralizedIdentifier::Web("localhost%3A8080:issuers:moh").document_url(),
+            "https://localhost:8080/issuers/moh/did.json"
+        );
+        assert_eq!(
+            DecentralizedIdentifier::Web("localhost%3a8080").document_url(),
+            "https://localhost:8080/.well-known/did.json"
+        );
+    }
+
+    #[cfg(feature = "proxy")]
+    #[test]
+    fn proxy_document_urls() {
+        assert_eq!(
+            crate::DidWebProxyResolver::new("http://127.0.0.1:8000/")
+                .document_url(DecentralizedIdentifier::Web("nzcp.identity.health.nz")),
+            "http://127.0.0.1:8000/nzcp.identity.health.nz/.well-known/did.json"
+        );
+    }
+
+    #[cfg(feature = "proxy")]
+    #[tokio::test]
+    async fn proxy_timeout() {
+        use std::time::Duration;
+
+        use crate::{DidWebProxyResolver, KeyResolver};
+
+        // accepts connections but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let resolver = DidWebProxyResolver::new(format!("http://{}", listener.local_addr().unwrap()))
+            .timeout(Duration::from_millis(100));
+
+        let error = resolver
+            .resolve_verifying_key(DecentralizedIdentifier::Web("nzcp.identity.health.nz"), "key-1")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, DecentralizedIdentifierError::ResolutionError(_)),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn jwk_verifying_keys() {
+        use DecentralizedIdentifierError::*;
+
+        let ec = |curve: &str, coordinate_length: usize| {
+            let point = match curve {
+                "P-384" => p384::ecdsa::SigningKey::from_slice(&[1; 48])
+                    .unwrap()
+                    .verifying_key()
+                    .to_encoded_point(false)
+                    .as_bytes()
+                    .to_vec(),
+                _ => p256::ecdsa::SigningKey::from_slice(&[1; 32])
+                    .unwrap()
+                    .verifying_key()
+                    .to_encoded_point(false)
+                    .as_bytes()
+                    .to_vec(),
+            };
+            let (x, y) = point[1..].split_at(point.len() / 2);
+            Params::EC(ECParams {
+                curve: Some(curve.to_owned()),
+                x_coordinate: Some(Base64urlUInt(x[..coordinate_length.min(x.len())].to_vec())),
+                y_coordinate: Some(Base64urlUInt(y.to_vec())),
+                ecc_private_key: None,
+            })
+        };
+        let okp = |curve: &str| {
+            Params::OKP(OctetParams {
+                curve: curve.to_owned(),
+                public_key: Base64urlUInt(
+                    ed25519_dalek::SigningKey::from_bytes(&[1; 32])
+                        .verifying_key()
+                        .to_bytes()
+                        .to_vec(),
+                ),
+                private_key: None,
+            })
+        };
+
+        let algorithm = |params| verifying_key_from_jwk(&params).map(|key| key.algorithm());
+        assert_eq!(algorithm(ec("P-256", 32)), Ok(SignatureAlgorithm::Es256));
+        assert_eq!(algorithm(ec("P-384", 48)), Ok(SignatureAlgorithm::Es384));
+        assert_eq!(algorithm(okp("Ed25519")), Ok(SignatureAlgorithm::EdDsa));
+
+        // a P-256 point claiming to be another curve
+        assert_eq!(algorithm(ec("P-521", 32)), Err(InvalidJWK));
+        assert_eq!(algorithm(ec("P-256", 31)), Err(InvalidJWK));
+        assert_eq!(algorithm(ec("secp256k1", 32)), Err(JWKWrongCurve));
+        assert_eq!(algorithm(okp("X25519")), Err(JWKWrongCurve));
     }
 }
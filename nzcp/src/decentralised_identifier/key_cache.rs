@@ -0,0 +1,258 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, PoisonError, RwLock, RwLockWriteGuard},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use super::{
+    resolver::{BlockingKeyResolver, DidWebResolver, KeyResolver},
+    DecentralizedIdentifier, DecentralizedIdentifierError,
+};
+use crate::payload::cose::signature::verify::VerifyingKey;
+
+type CacheKey = (String, String);
+
+/// A thread-safe cache of verifying keys resolved by another resolver, keyed on the issuer DID and key ID (`kid`).
+///
+/// Resolved keys are kept for the TTL, while key IDs missing from the issuer's DID document are negatively cached for
+/// the negative TTL. Other resolution failures (e.g. network errors) are never cached.
+///
+/// Clones share the same entries, so a single cache can be shared between tasks and threads.
+#[derive(Debug)]
+pub struct KeyCache<R = DidWebResolver> {
+    resolver: Arc<R>,
+    ttl: Duration,
+    negative_ttl: Duration,
+    entries: Arc<RwLock<HashMap<CacheKey, CacheEntry>>>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    result: Result<VerifyingKey, DecentralizedIdentifierError>,
+    expires_at: Instant,
+}
+
+impl KeyCache {
+    /// A cache of keys resolved online from the issuers' `did:web` DID documents.
+    pub fn new(ttl: Duration, negative_ttl: Duration) -> Self {
+        KeyCache::with_resolver(DidWebResolver, ttl, negative_ttl)
+    }
+}
+
+impl<R> KeyCache<R> {
+    /// A cache of keys resolved by the given resolver.
+    pub fn with_resolver(resolver: R, ttl: Duration, negative_ttl: Duration) -> Self {
+        KeyCache {
+            resolver: Arc::new(resolver),
+            ttl,
+            negative_ttl,
+            entries: Arc::default(),
+        }
+    }
+
+    /// Remove the cached entry for a single key ID, e.g. after a key rotation.
+    pub fn invalidate(&self, issuer: DecentralizedIdentifier<'_>, kid: &str) {
+        self.write().remove(&cache_key(issuer, kid));
+    }
+
+    /// Remove all cached entries for an issuer.
+    pub fn invalidate_issuer(&self, issuer: DecentralizedIdentifier<'_>) {
+        let did = issuer.to_string();
+        self.write().retain(|(entry_did, _), _| *entry_did != did);
+    }
+
+    /// Remove all cached entries.
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    fn get(&self, key: &CacheKey, now: Instant) -> Option<Result<VerifyingKey, DecentralizedIdentifierError>> {
+        let entries = self.entries.read().unwrap_or_else(PoisonError::into_inner);
+        entries
+            .get(key)
+            .filter(|entry| now < entry.expires_at)
+            .map(|entry| entry.result.clone())
+    }
+
+    fn insert(&self, key: CacheKey, result: Result<VerifyingKey, DecentralizedIdentifierError>, now: Instant) {
+        use DecentralizedIdentifierError::*;
+
+        let ttl = match result {
+            Ok(_) => self.ttl,
+            Err(MissingAssertionMethod(_) | MissingVerificationMethod(_)) => self.negative_ttl,
+            Err(_) => return,
+        };
+
+        let mut entries = self.write();
+        // drop stale entries so the cache doesn't grow unbounded with rotated keys
+        entries.retain(|_, entry| now < entry.expires_at);
+        entries.insert(
+            key,
+            CacheEntry {
+                result,
+                expires_at: now + ttl,
+            },
+        );
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<CacheKey, CacheEntry>> {
+        self.entries.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn cache_key(issuer: DecentralizedIdentifier<'_>, kid: &str) -> CacheKey {
+    (issuer.to_string(), kid.to_owned())
+}
+
+impl<R> Clone for KeyCache<R> {
+    fn clone(&self) -> Self {
+        KeyCache {
+            resolver: Arc::clone(&self.resolver),
+            ttl: self.ttl,
+            negative_ttl: self.negative_ttl,
+            entries: Arc::clone(&self.entries),
+        }
+    }
+}
+
+/// Gets the verifying key from the cache, resolving it if it is missing or stale.
+#[async_trait]
+impl<R: KeyResolver> KeyResolver for KeyCache<R> {
+    async fn resolve_verifying_key(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+        let key = cache_key(issuer, kid);
+
+        if let Some(result) = self.get(&key, Instant::now()) {
+            return result;
+        }
+
+        let result = self.resolver.resolve_verifying_key(issuer, kid).await;
+        self.insert(key, result.clone(), Instant::now());
+        result
+    }
+}
+
+/// Gets the verifying key from the cache, resolving it if it is missing or stale.
+impl<R: BlockingKeyResolver> BlockingKeyResolver for KeyCache<R> {
+    fn resolve_verifying_key_blocking(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+        let key = cache_key(issuer, kid);
+
+        if let Some(result) = self.get(&key, Instant::now()) {
+            return result;
+        }
+
+        let result = self.resolver.resolve_verifying_key_blocking(issuer, kid);
+        self.insert(key, result.clone(), Instant::now());
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use p256::ecdsa::SigningKey;
+
+    use super::*;
+
+    const EXAMPLE_ISSUER: DecentralizedIdentifier<'static> = DecentralizedIdentifier::Web("nzcp.covid19.health.nz");
+
+    fn verifying_key() -> VerifyingKey {
+        (*SigningKey::from_slice(&[7; 32]).unwrap().verifying_key()).into()
+    }
+
+    #[test]
+    fn expires_after_ttl() {
+        let cache = KeyCache::new(Duration::from_secs(60), Duration::from_secs(10));
+        let key = cache_key(EXAMPLE_ISSUER, "key-1");
+        let now = Instant::now();
+
+        assert_eq!(cache.get(&key, now), None);
+
+        cache.insert(key.clone(), Ok(verifying_key()), now);
+        assert_eq!(
+            cache.get(&key, now + Duration::from_secs(59)),
+            Some(Ok(verifying_key()))
+        );
+        assert_eq!(cache.get(&key, now + Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn negatively_caches_missing_keys() {
+        use DecentralizedIdentifierError::*;
+
+        let cache = KeyCache::new(Duration::from_secs(60), Duration::from_secs(10));
+        let key = cache_key(EXAMPLE_ISSUER, "key-2");
+        let now = Instant::now();
+        let missing = MissingAssertionMethod(String::from("did:web:nzcp.covid19.health.nz#key-2"));
+
+        cache.insert(key.clone(), Err(missing.clone()), now);
+        assert_eq!(cache.get(&key, now + Duration::from_secs(9)), Some(Err(missing)));
+        assert_eq!(cache.get(&key, now + Duration::from_secs(10)), None);
+
+        let key = cache_key(EXAMPLE_ISSUER, "key-3");
+        cache.insert(key.clone(), Err(ResolutionError(String::from("timed out"))), now);
+        assert_eq!(cache.get(&key, now), None);
+    }
+
+    #[test]
+    fn invalidates_entries() {
+        let cache = KeyCache::new(Duration::from_secs(60), Duration::from_secs(10));
+        let now = Instant::now();
+
+        cache.insert(cache_key(EXAMPLE_ISSUER, "key-1"), Ok(verifying_key()), now);
+        cache.insert(cache_key(EXAMPLE_ISSUER, "key-2"), Ok(verifying_key()), now);
+
+        cache.invalidate(EXAMPLE_ISSUER, "key-1");
+        assert_eq!(cache.get(&cache_key(EXAMPLE_ISSUER, "key-1"), now), None);
+        assert!(cache.get(&cache_key(EXAMPLE_ISSUER, "key-2"), now).is_some());
+
+        cache.invalidate_issuer(EXAMPLE_ISSUER);
+        assert_eq!(cache.get(&cache_key(EXAMPLE_ISSUER, "key-2"), now), None);
+    }
+
+    #[derive(Debug, Default)]
+    struct CountingResolver(AtomicUsize);
+
+    #[async_trait]
+    impl KeyResolver for CountingResolver {
+        async fn resolve_verifying_key(
+            &self,
+            _issuer: DecentralizedIdentifier<'_>,
+            _kid: &str,
+        ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(verifying_key())
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_once_per_key() {
+        let cache = KeyCache::with_resolver(
+            CountingResolver::default(),
+            Duration::from_secs(60),
+            Duration::from_secs(10),
+        );
+
+        for _ in 0..3 {
+            assert_eq!(
+                cache.resolve_verifying_key(EXAMPLE_ISSUER, "key-1").await,
+                Ok(verifying_key())
+            );
+        }
+        assert_eq!(cache.resolver.0.load(Ordering::SeqCst), 1);
+
+        cache.invalidate(EXAMPLE_ISSUER, "key-1");
+        cache.resolve_verifying_key(EXAMPLE_ISSUER, "key-1").await.unwrap();
+        assert_eq!(cache.resolver.0.load(Ordering::SeqCst), 2);
+    }
+}
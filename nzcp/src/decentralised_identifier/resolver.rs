@@ -0,0 +1,168 @@
+use std::sync::Arc;
+#[cfg(feature = "proxy")]
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+#[cfg(feature = "proxy")]
+use super::fetch_document;
+use super::{DecentralizedIdentifier, DecentralizedIdentifierError};
+use crate::payload::cose::signature::verify::VerifyingKey;
+
+/// Resolves the verifying key an issuer signed a pass with, given the issuer and the key ID (`kid`) from the pass.
+///
+/// Implement this to plug in your own HTTP client, a local mock server, an in-memory map or a caching layer. The
+/// crate provides `DidWebResolver` (online), `TrustStore` (offline), `KeyCache` (caching another resolver) and, with the
+/// `proxy` feature, `DidWebProxyResolver` (a stand-in DID server).
+#[async_trait]
+pub trait KeyResolver: Send + Sync {
+    async fn resolve_verifying_key(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError>;
+}
+
+/// Resolves verifying keys online from the issuer's `did:web` DID document.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DidWebResolver;
+
+#[async_trait]
+impl KeyResolver for DidWebResolver {
+    async fn resolve_verifying_key(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+        issuer.resolve_verifying_key(kid).await
+    }
+}
+
+/// How long the proxy resolver waits to connect to the DID server, and for the whole request, by default.
+#[cfg(feature = "proxy")]
+const PROXY_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+#[cfg(feature = "proxy")]
+const PROXY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Resolves verifying keys from `did:web` DID documents served by a stand-in DID server, such as a local mock server
+/// in tests.
+///
+/// The document for `did:web:example.com` is fetched from `{base_url}/example.com/.well-known/did.json` rather than
+/// `https://example.com/.well-known/did.json`. Requests time out after 10 seconds (or 5 seconds to connect) by default,
+/// so an unresponsive DID server fails resolution rather than hanging it.
+#[cfg(feature = "proxy")]
+#[derive(Debug, Clone)]
+pub struct DidWebProxyResolver {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "proxy")]
+impl DidWebProxyResolver {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        let mut base_url = base_url.into();
+        if base_url.ends_with('/') {
+            base_url.pop();
+        }
+
+        DidWebProxyResolver {
+            base_url,
+            client: proxy_client(PROXY_CONNECT_TIMEOUT, PROXY_TIMEOUT),
+        }
+    }
+
+    /// Fetch DID documents with the client, e.g. to share its connection pool. The client's own timeouts apply.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Fail resolution if a DID document takes longer than `timeout` to fetch, including connecting.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.client = proxy_client(PROXY_CONNECT_TIMEOUT.min(timeout), timeout);
+        self
+    }
+
+    /// The URL the issuer's DID document is fetched from.
+    pub fn document_url(&self, issuer: DecentralizedIdentifier<'_>) -> String {
+        format!("{}/{}", self.base_url, issuer.document_path())
+    }
+}
+
+#[cfg(feature = "proxy")]
+fn proxy_client(connect_timeout: Duration, timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .connect_timeout(connect_timeout)
+        .timeout(timeout)
+        .build()
+        .expect("the HTTP client has a valid configuration")
+}
+
+#[cfg(feature = "proxy")]
+#[async_trait]
+impl KeyResolver for DidWebProxyResolver {
+    async fn resolve_verifying_key(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+        let document = fetch_document(&self.client, &self.document_url(issuer)).await?;
+
+        issuer.verifying_key_from_document(&document, kid)
+    }
+}
+
+#[async_trait]
+impl<R: KeyResolver + ?Sized> KeyResolver for Box<R> {
+    async fn resolve_verifying_key(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+        (**self).resolve_verifying_key(issuer, kid).await
+    }
+}
+
+#[async_trait]
+impl<R: KeyResolver + ?Sized> KeyResolver for Arc<R> {
+    async fn resolve_verifying_key(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+        (**self).resolve_verifying_key(issuer, kid).await
+    }
+}
+
+/// Resolves the verifying key an issuer signed a pass with without blocking on any I/O that needs an async runtime,
+/// allowing passes to be verified synchronously.
+///
+/// The crate provides `TrustStore` (offline, without its online fallback) and `KeyCache` (caching another blocking
+/// resolver).
+pub trait BlockingKeyResolver: Send + Sync {
+    fn resolve_verifying_key_blocking(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError>;
+}
+
+impl<R: BlockingKeyResolver + ?Sized> BlockingKeyResolver for Box<R> {
+    fn resolve_verifying_key_blocking(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+        (**self).resolve_verifying_key_blocking(issuer, kid)
+    }
+}
+
+impl<R: BlockingKeyResolver + ?Sized> BlockingKeyResolver for Arc<R> {
+    fn resolve_verifying_key_blocking(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+        (**self).resolve_verifying_key_blocking(issuer, kid)
+    }
+}
@@ -0,0 +1,157 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use async_trait::async_trait;
+use ssi::did::Document;
+
+use super::{
+    parse_document,
+    resolver::{BlockingKeyResolver, DidWebResolver, KeyResolver},
+    DecentralizedIdentifier, DecentralizedIdentifierError,
+};
+use crate::payload::cose::signature::verify::VerifyingKey;
+
+/// The published DID document of the MoH `did:web:nzcp.identity.health.nz` issuer.
+const MINISTRY_OF_HEALTH_DOCUMENT: &str = include_str!("../../did/nzcp.identity.health.nz.json");
+
+/// An offline store of trusted DID documents, so verifying keys can be resolved without network access.
+///
+/// Issuers or key IDs missing from the store are rejected, unless online resolution has been explicitly enabled as a
+/// fallback.
+#[derive(Debug, Clone, Default)]
+pub struct TrustStore {
+    documents: HashMap<String, Document>,
+    online_fallback: bool,
+}
+
+impl TrustStore {
+    /// An empty trust store.
+    pub fn new() -> Self {
+        TrustStore::default()
+    }
+
+    /// A trust store seeded with the MoH DID document bundled with this crate.
+    pub fn ministry_of_health() -> Self {
+        let mut trust_store = TrustStore::new();
+        trust_store
+            .add_document_json(MINISTRY_OF_HEALTH_DOCUMENT)
+            .expect("invalid bundled MoH DID document");
+        trust_store
+    }
+
+    /// Resolve issuers or key IDs missing from the store online (disabled by default).
+    pub fn with_online_fallback(mut self, online_fallback: bool) -> Self {
+        self.online_fallback = online_fallback;
+        self
+    }
+
+    /// Add a DID document (e.g. the contents of `/.well-known/did.json`), replacing any document with the same `id`.
+    pub fn add_document_json(&mut self, json: &str) -> Result<(), DecentralizedIdentifierError> {
+        let document = parse_document(json.as_bytes())?.ok_or(DecentralizedIdentifierError::EmptyDocument)?;
+        self.documents.insert(document.id.clone(), document);
+        Ok(())
+    }
+
+    /// Add a DID document from a JSON file, replacing any document with the same `id`.
+    pub fn add_document_file(&mut self, path: impl AsRef<Path>) -> Result<(), DecentralizedIdentifierError> {
+        let json =
+            fs::read_to_string(path).map_err(|err| DecentralizedIdentifierError::ResolutionError(err.to_string()))?;
+        self.add_document_json(&json)
+    }
+
+    /// Get the verifying key from the stored DID documents, without network access.
+    pub fn verifying_key(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+        let document = self
+            .documents
+            .get(&issuer.did())
+            .ok_or_else(|| DecentralizedIdentifierError::MissingDocument(issuer.did()))?;
+
+        issuer.verifying_key_from_document(document, kid)
+    }
+}
+
+/// Gets the verifying key from the stored DID documents, falling back to online resolution if enabled.
+#[async_trait]
+impl KeyResolver for TrustStore {
+    async fn resolve_verifying_key(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+        use DecentralizedIdentifierError::*;
+
+        match self.verifying_key(issuer, kid) {
+            Err(MissingDocument(_) | MissingAssertionMethod(_) | MissingVerificationMethod(_))
+                if self.online_fallback =>
+            {
+                DidWebResolver.resolve_verifying_key(issuer, kid).await
+            }
+            result => result,
+        }
+    }
+}
+
+/// Gets the verifying key from the stored DID documents only, as online resolution can't be done synchronously.
+impl BlockingKeyResolver for TrustStore {
+    fn resolve_verifying_key_blocking(
+        &self,
+        issuer: DecentralizedIdentifier<'_>,
+        kid: &str,
+    ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+        self.verifying_key(issuer, kid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::{barcode::QrBarcode, cose::CoseStructure};
+
+    const EXAMPLE_ISSUER: DecentralizedIdentifier<'static> = DecentralizedIdentifier::Web("nzcp.covid19.health.nz");
+
+    const EXAMPLE_DOCUMENT: &str = include_str!("../../tests/did/nzcp.covid19.health.nz.json");
+
+    #[test]
+    fn bundled_ministry_of_health_document() {
+        let trust_store = TrustStore::ministry_of_health();
+
+        assert!(trust_store
+            .verifying_key(DecentralizedIdentifier::Web("nzcp.identity.health.nz"), "z12Kf7UQ")
+            .is_ok());
+    }
+
+    #[test]
+    fn verify_example_offline() {
+        let mut trust_store = TrustStore::new();
+        trust_store.add_document_json(EXAMPLE_DOCUMENT).unwrap();
+        let verifying_key = trust_store.verifying_key(EXAMPLE_ISSUER, "key-1").unwrap();
+
+        // https://nzcp.covid19.health.nz/#valid-worked-example
+        let barcode: QrBarcode = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX".parse().unwrap();
+        let cose: CoseStructure<'_, serde_cbor::Value> = serde_cbor::from_slice(&barcode.cbor).unwrap();
+
+        cose.verify_signature(&verifying_key).unwrap();
+    }
+
+    #[test]
+    fn missing_key() {
+        use DecentralizedIdentifierError::*;
+
+        let mut trust_store = TrustStore::new();
+        trust_store.add_document_json(EXAMPLE_DOCUMENT).unwrap();
+
+        assert_eq!(
+            trust_store.verifying_key(EXAMPLE_ISSUER, "key-2"),
+            Err(MissingAssertionMethod(String::from(
+                "did:web:nzcp.covid19.health.nz#key-2"
+            )))
+        );
+        assert_eq!(
+            trust_store.verifying_key(DecentralizedIdentifier::Web("nzcp.identity.health.nz"), "z12Kf7UQ"),
+            Err(MissingDocument(String::from("did:web:nzcp.identity.health.nz")))
+        );
+    }
+}
@@ -1,21 +1,32 @@
+use std::fmt;
+
+use serde::{Serialize, Serializer};
 use thiserror::Error;
 
 pub use crate::{
     decentralised_identifier::DecentralizedIdentifierError,
+    issue::IssueError,
     payload::{
-        barcode::QrBarcodeError, cose::signature::verify::CoseVerificationError, cwt::validation::CwtValidationError,
+        barcode::{QrBarcodeError, QrImageError, QrRenderError},
+        cose::signature::verify::CoseVerificationError,
+        cwt::validation::{CwtValidationError, LeewayError},
+        error::{Claim, PayloadError, ProtectedHeader},
     },
 };
 
 /// An error that occurred while verifying a pass barcode.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum NzcpError {
+    /// No pass QR code could be found in the image.
+    #[error("Invalid QR image: {0}")]
+    QrImage(#[from] QrImageError),
     /// The barcode string was invalid.
     #[error("Invalid QR barcode: {0:?}")]
     QrBarcode(#[from] QrBarcodeError),
     /// The payload could not be deserialized correctly.
     #[error("Invalid payload: {0:?}")]
-    InvalidPayload(#[from] serde_cbor::Error),
+    InvalidPayload(#[from] PayloadError),
     /// The signature of the COSE payload was invalid, or the issuer was not trusted.
     #[error("Invalid signature: {0:?}")]
     InvalidSignature(#[from] CoseVerificationError),
@@ -24,12 +35,36 @@ pub enum NzcpError {
     InvalidCWT(#[from] CwtValidationError),
 }
 
+impl NzcpError {
+    /// The stable code for the error, for deciding what to show the user without matching on messages.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            NzcpError::QrImage(error) => error.code(),
+            NzcpError::QrBarcode(error) => error.code(),
+            NzcpError::InvalidPayload(error) => error.code(),
+            NzcpError::InvalidSignature(error) => error.code(),
+            NzcpError::InvalidCWT(error) => error.code(),
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        self.code().category()
+    }
+}
+
+impl From<serde_cbor::Error> for NzcpError {
+    fn from(error: serde_cbor::Error) -> Self {
+        NzcpError::InvalidPayload(PayloadError::Cbor(error))
+    }
+}
+
 impl PartialEq for NzcpError {
     fn eq(&self, other: &Self) -> bool {
         use NzcpError::*;
         match (self, other) {
+            (QrImage(l0), QrImage(r0)) => l0 == r0,
             (QrBarcode(l0), QrBarcode(r0)) => l0 == r0,
-            (InvalidPayload(l0), InvalidPayload(r0)) => l0.to_string() == r0.to_string(),
+            (InvalidPayload(l0), InvalidPayload(r0)) => l0 == r0,
             (InvalidSignature(l0), InvalidSignature(r0)) => l0 == r0,
             (InvalidCWT(l0), InvalidCWT(r0)) => l0 == r0,
             _ => false,
@@ -38,3 +73,373 @@ impl PartialEq for NzcpError {
 }
 
 impl Eq for NzcpError {}
+
+/// Declares the error codes, generating [`ErrorCode::ALL`] and [`ErrorCode::as_str`] from the one list so that neither
+/// can miss a code.
+macro_rules! error_codes {
+    (
+        $(#[$attribute:meta])*
+        pub enum $name:ident {
+            $($(#[doc = $doc:literal])* $variant:ident => $code:literal,)*
+        }
+    ) => {
+        $(#[$attribute])*
+        pub enum $name {
+            $($(#[doc = $doc])* $variant,)*
+        }
+
+        impl $name {
+            pub const ALL: [$name; [$($code),*].len()] = [$($name::$variant),*];
+
+            /// The code string, e.g. `NZCP-EXPIRED`.
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $code,)*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    /// A stable, machine-readable code for each way verification can fail, which won't change when error messages do.
+    ///
+    /// Serializes as the code string (e.g. `"NZCP-EXPIRED"`).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum ErrorCode {
+        /// `NZCP-IMAGE-INVALID`: The image isn't a PNG or JPEG which could be read.
+        ImageInvalid => "NZCP-IMAGE-INVALID",
+        /// `NZCP-IMAGE-NO-QR-CODE`: No NZCP QR code was found in the image.
+        ImageNoQrCode => "NZCP-IMAGE-NO-QR-CODE",
+        /// `NZCP-BARCODE-PREFIX`: The barcode doesn't start with `NZCP:/`.
+        BarcodePrefix => "NZCP-BARCODE-PREFIX",
+        /// `NZCP-BARCODE-VERSION`: The barcode's version identifier isn't a number.
+        BarcodeVersion => "NZCP-BARCODE-VERSION",
+        /// `NZCP-BARCODE-UNSUPPORTED-VERSION`: The barcode's version identifier is one this crate doesn't support (yet).
+        BarcodeUnsupportedVersion => "NZCP-BARCODE-UNSUPPORTED-VERSION",
+        /// `NZCP-BARCODE-ENCODING`: The barcode's payload isn't valid base32.
+        BarcodeEncoding => "NZCP-BARCODE-ENCODING",
+        /// `NZCP-BARCODE-WHITESPACE`: The barcode has surrounding whitespace.
+        BarcodeWhitespace => "NZCP-BARCODE-WHITESPACE",
+        /// `NZCP-BARCODE-LOWERCASE`: The barcode isn't upper case.
+        BarcodeLowercase => "NZCP-BARCODE-LOWERCASE",
+        /// `NZCP-BARCODE-PADDING`: The barcode's base32 payload is padded with `=`.
+        BarcodePadding => "NZCP-BARCODE-PADDING",
+        /// `NZCP-INVALID-PAYLOAD`: The payload isn't valid CBOR, or a value in it has the wrong CBOR type.
+        InvalidPayload => "NZCP-INVALID-PAYLOAD",
+        /// `NZCP-INVALID-SIGN-STRUCTURE`: The payload isn't tagged as a `COSE_Sign1` structure.
+        InvalidSignStructure => "NZCP-INVALID-SIGN-STRUCTURE",
+        /// `NZCP-MISSING-COSE-SEGMENT`: The COSE structure is missing a segment.
+        MissingCoseSegment => "NZCP-MISSING-COSE-SEGMENT",
+        /// `NZCP-MISSING-PROTECTED-HEADER`: The `kid` or `alg` protected header is missing.
+        MissingProtectedHeader => "NZCP-MISSING-PROTECTED-HEADER",
+        /// `NZCP-UNKNOWN-PROTECTED-HEADER`: There's a protected header other than `kid` and `alg`.
+        UnknownProtectedHeader => "NZCP-UNKNOWN-PROTECTED-HEADER",
+        /// `NZCP-UNSUPPORTED-ALGORITHM`: The signature algorithm isn't supported, or isn't allowed (by default only ES256 is).
+        UnsupportedAlgorithm => "NZCP-UNSUPPORTED-ALGORITHM",
+        /// `NZCP-MISSING-CLAIM`: A CWT claim is missing.
+        MissingClaim => "NZCP-MISSING-CLAIM",
+        /// `NZCP-UNKNOWN-CLAIM`: There's a CWT claim other than those in the spec.
+        UnknownClaim => "NZCP-UNKNOWN-CLAIM",
+        /// `NZCP-INVALID-ISSUER`: The issuer isn't a `did:web` DID.
+        InvalidIssuer => "NZCP-INVALID-ISSUER",
+        /// `NZCP-INVALID-CWT-TOKEN-ID`: The CWT token ID isn't a 16 byte UUID.
+        InvalidCwtTokenId => "NZCP-INVALID-CWT-TOKEN-ID",
+        /// `NZCP-INVALID-DATE`: The not before or expiry date is out of range.
+        InvalidDate => "NZCP-INVALID-DATE",
+        /// `NZCP-SIGNATURE-INVALID`: The signature isn't valid for the issuer's key.
+        SignatureInvalid => "NZCP-SIGNATURE-INVALID",
+        /// `NZCP-UNTRUSTED-ISSUER`: The issuer isn't trusted.
+        UntrustedIssuer => "NZCP-UNTRUSTED-ISSUER",
+        /// `NZCP-KEY-ALGORITHM-MISMATCH`: The issuer's key is for a different signature algorithm than the pass's.
+        KeyAlgorithmMismatch => "NZCP-KEY-ALGORITHM-MISMATCH",
+        /// `NZCP-UNSUPPORTED-VERSION`: The credential version is one this crate doesn't support (yet).
+        UnsupportedVersion => "NZCP-UNSUPPORTED-VERSION",
+        /// `NZCP-MALFORMED-VERSION`: The credential version isn't `MAJOR.MINOR.PATCH`.
+        MalformedVersion => "NZCP-MALFORMED-VERSION",
+        /// `NZCP-INVALID-CONTEXT`: The credential's JSON-LD contexts don't match the pass type.
+        InvalidContext => "NZCP-INVALID-CONTEXT",
+        /// `NZCP-INVALID-TYPE`: The credential's type doesn't match the pass type.
+        InvalidType => "NZCP-INVALID-TYPE",
+        /// `NZCP-INVALID-CREDENTIAL-SUBJECT`: The credential subject isn't valid for the credential's pass type.
+        InvalidCredentialSubject => "NZCP-INVALID-CREDENTIAL-SUBJECT",
+        /// `NZCP-NOT-YET-VALID`: The pass isn't active yet.
+        NotYetValid => "NZCP-NOT-YET-VALID",
+        /// `NZCP-EXPIRED`: The pass has expired.
+        Expired => "NZCP-EXPIRED",
+        /// `NZCP-DID-RESOLUTION`: The issuer's DID document couldn't be resolved.
+        DidResolution => "NZCP-DID-RESOLUTION",
+        /// `NZCP-DID-EMPTY-DOCUMENT`: The issuer's DID document was empty.
+        DidEmptyDocument => "NZCP-DID-EMPTY-DOCUMENT",
+        /// `NZCP-DID-MISSING-DOCUMENT`: The issuer's DID document isn't in the trust store.
+        DidMissingDocument => "NZCP-DID-MISSING-DOCUMENT",
+        /// `NZCP-DID-MISSING-ASSERTION-METHODS`: The issuer's DID document has no `assertionMethod`s.
+        DidMissingAssertionMethods => "NZCP-DID-MISSING-ASSERTION-METHODS",
+        /// `NZCP-KEY-NOT-FOUND`: The key ID isn't an `assertionMethod` of the issuer's DID document.
+        KeyNotFound => "NZCP-KEY-NOT-FOUND",
+        /// `NZCP-DID-MISSING-VERIFICATION-METHODS`: The issuer's DID document has no `verificationMethod`s.
+        DidMissingVerificationMethods => "NZCP-DID-MISSING-VERIFICATION-METHODS",
+        /// `NZCP-DID-MISSING-VERIFICATION-METHOD`: The key ID isn't a `verificationMethod` of the issuer's DID document.
+        DidMissingVerificationMethod => "NZCP-DID-MISSING-VERIFICATION-METHOD",
+        /// `NZCP-DID-KEY-TYPE`: The key isn't a `JsonWebKey2020`.
+        DidKeyType => "NZCP-DID-KEY-TYPE",
+        /// `NZCP-DID-MISSING-JWK`: The key has no `publicKeyJwk`.
+        DidMissingJwk => "NZCP-DID-MISSING-JWK",
+        /// `NZCP-DID-JWK-NOT-EC`: The key isn't an elliptic curve or octet key pair key.
+        DidJwkNotEllipticCurve => "NZCP-DID-JWK-NOT-EC",
+        /// `NZCP-DID-JWK-MISSING-X`: The key has no x coordinate.
+        DidJwkMissingX => "NZCP-DID-JWK-MISSING-X",
+        /// `NZCP-DID-JWK-MISSING-Y`: The key has no y coordinate.
+        DidJwkMissingY => "NZCP-DID-JWK-MISSING-Y",
+        /// `NZCP-DID-JWK-WRONG-CURVE`: The key's curve isn't P-256, P-384, P-521 or Ed25519.
+        DidJwkWrongCurve => "NZCP-DID-JWK-WRONG-CURVE",
+        /// `NZCP-DID-JWK-INVALID`: The key isn't a valid public key for its curve.
+        DidJwkInvalid => "NZCP-DID-JWK-INVALID",
+    }
+}
+
+impl ErrorCode {
+    pub fn category(&self) -> ErrorCategory {
+        use ErrorCode::*;
+
+        match self {
+            ImageInvalid
+            | ImageNoQrCode
+            | BarcodePrefix
+            | BarcodeVersion
+            | BarcodeEncoding
+            | BarcodeWhitespace
+            | BarcodeLowercase
+            | BarcodePadding
+            | InvalidPayload
+            | InvalidSignStructure
+            | MissingCoseSegment
+            | MissingProtectedHeader
+            | UnknownProtectedHeader
+            | UnsupportedAlgorithm
+            | MissingClaim
+            | UnknownClaim
+            | InvalidIssuer
+            | InvalidCwtTokenId
+            | InvalidDate
+            | MalformedVersion
+            | InvalidContext
+            | InvalidType
+            | InvalidCredentialSubject => ErrorCategory::Malformed,
+            SignatureInvalid
+            | UntrustedIssuer
+            | DidEmptyDocument
+            | KeyAlgorithmMismatch
+            | DidMissingDocument
+            | DidMissingAssertionMethods
+            | KeyNotFound
+            | DidMissingVerificationMethods
+            | DidMissingVerificationMethod
+            | DidKeyType
+            | DidMissingJwk
+            | DidJwkNotEllipticCurve
+            | DidJwkMissingX
+            | DidJwkMissingY
+            | DidJwkWrongCurve
+            | DidJwkInvalid => ErrorCategory::Untrusted,
+            NotYetValid => ErrorCategory::NotYetValid,
+            Expired => ErrorCategory::Expired,
+            DidResolution => ErrorCategory::Network,
+            BarcodeUnsupportedVersion | UnsupportedVersion => ErrorCategory::Unsupported,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// A coarse category of error codes, e.g. for metrics or choosing which message to show.
+///
+/// Serializes in kebab case (e.g. `"not-yet-valid"`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCategory {
+    /// The barcode or its contents don't meet the spec.
+    Malformed,
+    /// The pass isn't signed by a trusted issuer's key.
+    Untrusted,
+    Expired,
+    NotYetValid,
+    /// The issuer's DID document couldn't be resolved, so the pass may be valid when retried.
+    Network,
+    /// The pass is a newer version than this crate supports, so it may be valid but can't be verified until updated.
+    Unsupported,
+}
+
+impl QrImageError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            QrImageError::InvalidImage(_) => ErrorCode::ImageInvalid,
+            QrImageError::NoNzcpQrCode(_) => ErrorCode::ImageNoQrCode,
+        }
+    }
+}
+
+impl QrBarcodeError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            QrBarcodeError::InvalidBase32Character(..) | QrBarcodeError::InvalidBase32Length(_) => {
+                ErrorCode::BarcodeEncoding
+            }
+            QrBarcodeError::Base32Padding(_) => ErrorCode::BarcodePadding,
+            QrBarcodeError::Lowercase(_) => ErrorCode::BarcodeLowercase,
+            QrBarcodeError::Whitespace(_) => ErrorCode::BarcodeWhitespace,
+            QrBarcodeError::MalformedVersion => ErrorCode::BarcodeVersion,
+            QrBarcodeError::UnsupportedVersion(_) => ErrorCode::BarcodeUnsupportedVersion,
+            QrBarcodeError::MissingNzcpPrefix => ErrorCode::BarcodePrefix,
+        }
+    }
+}
+
+impl PayloadError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            PayloadError::Cbor(_) => ErrorCode::InvalidPayload,
+            PayloadError::SignStructure(_) => ErrorCode::InvalidSignStructure,
+            PayloadError::MissingCoseSegment(_) => ErrorCode::MissingCoseSegment,
+            PayloadError::MissingProtectedHeader(_) => ErrorCode::MissingProtectedHeader,
+            PayloadError::UnknownProtectedHeader(_) => ErrorCode::UnknownProtectedHeader,
+            PayloadError::UnsupportedAlgorithm(_) => ErrorCode::UnsupportedAlgorithm,
+            PayloadError::MissingClaim(_) => ErrorCode::MissingClaim,
+            PayloadError::UnknownClaim(_) => ErrorCode::UnknownClaim,
+            PayloadError::InvalidIssuer(_) => ErrorCode::InvalidIssuer,
+            PayloadError::InvalidCwtTokenId => ErrorCode::InvalidCwtTokenId,
+            PayloadError::InvalidDate(_) => ErrorCode::InvalidDate,
+        }
+    }
+}
+
+impl CoseVerificationError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CoseVerificationError::VerificationFailed => ErrorCode::SignatureInvalid,
+            CoseVerificationError::CwtValidation(error) => error.code(),
+            CoseVerificationError::UntrustedIssuer(_) => ErrorCode::UntrustedIssuer,
+            CoseVerificationError::DecentralizedIdentifierResolution(error) => error.code(),
+            CoseVerificationError::DisallowedAlgorithm(_) => ErrorCode::UnsupportedAlgorithm,
+            CoseVerificationError::KeyAlgorithmMismatch { .. } => ErrorCode::KeyAlgorithmMismatch,
+        }
+    }
+}
+
+impl CwtValidationError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CwtValidationError::NotYetActive(_) => ErrorCode::NotYetValid,
+            CwtValidationError::Expired(_) => ErrorCode::Expired,
+            CwtValidationError::UnsupportedVersion(_) => ErrorCode::UnsupportedVersion,
+            CwtValidationError::MalformedVersion(_) => ErrorCode::MalformedVersion,
+            CwtValidationError::InvalidContext(_) => ErrorCode::InvalidContext,
+            CwtValidationError::InvalidType(..) => ErrorCode::InvalidType,
+            CwtValidationError::InvalidCredentialSubject(..) => ErrorCode::InvalidCredentialSubject,
+        }
+    }
+}
+
+impl DecentralizedIdentifierError {
+    pub fn code(&self) -> ErrorCode {
+        use DecentralizedIdentifierError::*;
+
+        match self {
+            ResolutionError(_) => ErrorCode::DidResolution,
+            EmptyDocument => ErrorCode::DidEmptyDocument,
+            MissingAssertionMethods => ErrorCode::DidMissingAssertionMethods,
+            MissingAssertionMethod(_) => ErrorCode::KeyNotFound,
+            MissingVerificationMethods => ErrorCode::DidMissingVerificationMethods,
+            MissingVerificationMethod(_) => ErrorCode::DidMissingVerificationMethod,
+            NotJsonWebKey2020 => ErrorCode::DidKeyType,
+            MissingJWK => ErrorCode::DidMissingJwk,
+            JWKNotEllipticCurve => ErrorCode::DidJwkNotEllipticCurve,
+            JWKMissingX => ErrorCode::DidJwkMissingX,
+            JWKMissingY => ErrorCode::DidJwkMissingY,
+            JWKWrongCurve => ErrorCode::DidJwkWrongCurve,
+            InvalidJWK => ErrorCode::DidJwkInvalid,
+            MissingDocument(_) => ErrorCode::DidMissingDocument,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::SignatureAlgorithm;
+
+    #[test]
+    fn codes_are_unique() {
+        let codes: HashSet<_> = ErrorCode::ALL.iter().map(ErrorCode::as_str).collect();
+
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+        assert!(codes.iter().all(|code| code.starts_with("NZCP-")));
+    }
+
+    #[test]
+    fn nested_error_codes() {
+        let error = NzcpError::InvalidSignature(CoseVerificationError::CwtValidation(CwtValidationError::Expired(
+            Utc::now(),
+        )));
+        assert_eq!(error.code(), ErrorCode::Expired);
+        assert_eq!(error.category(), ErrorCategory::Expired);
+
+        let error = NzcpError::InvalidSignature(CoseVerificationError::DecentralizedIdentifierResolution(
+            DecentralizedIdentifierError::ResolutionError(String::from("timed out")),
+        ));
+        assert_eq!(error.code(), ErrorCode::DidResolution);
+        assert_eq!(error.category(), ErrorCategory::Network);
+
+        // an empty document was served, so scanning again won't help
+        let error = NzcpError::InvalidSignature(CoseVerificationError::DecentralizedIdentifierResolution(
+            DecentralizedIdentifierError::EmptyDocument,
+        ));
+        assert_eq!(error.code(), ErrorCode::DidEmptyDocument);
+        assert_eq!(error.category(), ErrorCategory::Untrusted);
+
+        let error = NzcpError::InvalidPayload(PayloadError::MissingClaim(Claim::VerifiableCredential));
+        assert_eq!(error.code(), ErrorCode::MissingClaim);
+        assert_eq!(error.category(), ErrorCategory::Malformed);
+
+        let error = NzcpError::QrBarcode(QrBarcodeError::UnsupportedVersion(2));
+        assert_eq!(error.code(), ErrorCode::BarcodeUnsupportedVersion);
+        assert_eq!(error.category(), ErrorCategory::Unsupported);
+
+        let error = NzcpError::QrBarcode(QrBarcodeError::MalformedVersion);
+        assert_eq!(error.code(), ErrorCode::BarcodeVersion);
+        assert_eq!(error.category(), ErrorCategory::Malformed);
+
+        let error = NzcpError::InvalidSignature(CoseVerificationError::KeyAlgorithmMismatch {
+            algorithm: SignatureAlgorithm::Es384,
+            key_algorithm: SignatureAlgorithm::Es256,
+        });
+        assert_eq!(error.code(), ErrorCode::KeyAlgorithmMismatch);
+        assert_eq!(error.category(), ErrorCategory::Untrusted);
+    }
+
+    #[test]
+    fn serialize_codes() {
+        assert_eq!(serde_json::to_string(&ErrorCode::Expired).unwrap(), r#""NZCP-EXPIRED""#);
+        assert_eq!(
+            serde_json::to_string(&ErrorCategory::NotYetValid).unwrap(),
+            r#""not-yet-valid""#
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use p256::ecdsa::SigningKey;
+use serde::Serialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    decentralised_identifier::DecentralizedIdentifier,
+    pass::Pass,
+    payload::{
+        barcode::QrBarcode,
+        cose::{
+            protected_headers::ProtectedHeaders,
+            signature::{sign::sign1, SignatureAlgorithm},
+        },
+        cwt::CwtClaims,
+    },
+};
+
+/// An error that occurred while issuing a pass barcode.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum IssueError {
+    /// The expiry date was not after the not before date.
+    #[error("pass expiry ({expiry:?}) must be after its not before date ({not_before:?})")]
+    InvalidValidityPeriod {
+        not_before: DateTime<Utc>,
+        expiry: DateTime<Utc>,
+    },
+    /// The pass could not be serialized to CBOR.
+    #[error("pass could not be serialized: {0:?}")]
+    Serialization(#[from] serde_cbor::Error),
+}
+
+/// Issues pass barcodes signed with an ES256 (P-256) key.
+///
+/// The `kid` must reference an `assertionMethod` in the issuer's DID document containing the public half of the
+/// signing key, otherwise the issued passes will fail verification.
+///
+/// ```ignore
+/// let issuer = PassIssuer::new(DecentralizedIdentifier::Web("nzcp.covid19.health.nz"), "key-1", signing_key);
+/// let barcode = issuer.issue_pass_uri(&pass, cwt_token_id, not_before, expiry)?;
+/// ```
+pub struct PassIssuer<'a> {
+    issuer: DecentralizedIdentifier<'a>,
+    kid: &'a str,
+    signing_key: SigningKey,
+}
+
+impl<'a> PassIssuer<'a> {
+    pub fn new(issuer: DecentralizedIdentifier<'a>, kid: &'a str, signing_key: SigningKey) -> Self {
+        PassIssuer {
+            issuer,
+            kid,
+            signing_key,
+        }
+    }
+
+    /// Sign a pass, returning the barcode URI (e.g. `NZCP:/1/2KCEVIQ...`) to be encoded into a QR code.
+    ///
+    /// The `cwt_token_id` should be unique for every issued pass.
+    pub fn issue_pass_uri<P: Pass + Serialize>(
+        &self,
+        pass: &P,
+        cwt_token_id: Uuid,
+        not_before: DateTime<Utc>,
+        expiry: DateTime<Utc>,
+    ) -> Result<String, IssueError> {
+        if expiry <= not_before {
+            return Err(IssueError::InvalidValidityPeriod { not_before, expiry });
+        }
+
+        let protected_headers = ProtectedHeaders {
+            kid: self.kid,
+            algorithm: SignatureAlgorithm::Es256,
+        };
+        let cwt_claims = CwtClaims::new(cwt_token_id, self.issuer, not_before, expiry, pass);
+
+        let cose = sign1(&protected_headers, &cwt_claims, &self.signing_key)?;
+
+        Ok(QrBarcode::new(cose).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+
+    use super::*;
+    use crate::{payload::cose::CoseStructure, Leeway, PublicCovidPass};
+
+    const EXAMPLE_ISSUER: DecentralizedIdentifier<'static> = DecentralizedIdentifier::Web("nzcp.covid19.health.nz");
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_slice(&[7; 32]).unwrap()
+    }
+
+    fn pass() -> PublicCovidPass {
+        PublicCovidPass {
+            given_name: String::from("Jack"),
+            family_name: Some(String::from("Sparrow")),
+            date_of_birth: NaiveDate::from_ymd(1960, 4, 16),
+        }
+    }
+
+    #[test]
+    fn issue_and_verify() {
+        let issuer = PassIssuer::new(EXAMPLE_ISSUER, "key-1", signing_key());
+        let now = Utc::now();
+
+        let barcode = issuer
+            .issue_pass_uri(&pass(), Uuid::nil(), now - Duration::days(1), now + Duration::days(1))
+            .unwrap();
+        assert!(barcode.starts_with("NZCP:/1/"));
+
+        let barcode: QrBarcode = barcode.parse().unwrap();
+        let cose: CoseStructure<'_, PublicCovidPass> = serde_cbor::from_slice(&barcode.cbor).unwrap();
+        let cwt = cose
+            .verified_claims_with_key(&(*signing_key().verifying_key()).into())
+            .unwrap();
+
+        let verified = cwt.validated_pass("key-1", now, Leeway::default()).unwrap();
+
+        assert_eq!(verified.pass, pass());
+        assert_eq!(verified.cwt_token_id, Uuid::nil());
+        assert_eq!(verified.issuer, "did:web:nzcp.covid19.health.nz");
+    }
+
+    #[test]
+    fn reject_invalid_validity_period() {
+        let issuer = PassIssuer::new(EXAMPLE_ISSUER, "key-1", signing_key());
+        let now = Utc::now();
+
+        let error = issuer.issue_pass_uri(&pass(), Uuid::nil(), now, now).unwrap_err();
+
+        assert!(matches!(error, IssueError::InvalidValidityPeriod { .. }))
+    }
+}
@@ -13,12 +13,46 @@
 //! let pass: PublicCovidPass = verify_pass_uri(barcode).await?;
 //! ```
 
+#[cfg(feature = "proxy")]
+pub use self::decentralised_identifier::resolver::DidWebProxyResolver;
+#[cfg(feature = "image")]
+pub use self::payload::barcode::image::{
+    barcode_uris_from_dynamic_image, barcode_uris_from_image, barcode_uris_from_image_file,
+};
+#[cfg(feature = "render")]
+pub use self::payload::barcode::render::QrCodeRenderer;
 pub use self::{
-    decentralised_identifier::DecentralizedIdentifier,
-    pass::{public_covid_pass::PublicCovidPass, verify_pass_uri, verify_pass_uri_with_trusted_issuers},
+    clock::{Clock, SystemClock},
+    decentralised_identifier::{
+        key_cache::KeyCache,
+        resolver::{BlockingKeyResolver, DidWebResolver, KeyResolver},
+        trust_store::TrustStore,
+        DecentralizedIdentifier,
+    },
+    issue::PassIssuer,
+    pass::{
+        any_pass::AnyPass, decode_pass_uri, public_covid_pass::PublicCovidPass, raw_pass::RawPass, verify_pass_uri,
+        verify_pass_uri_at, verify_pass_uri_blocking, verify_pass_uri_with_leeway, verify_pass_uri_with_resolver,
+        verify_pass_uri_with_trusted_issuers, verify_pass_uri_with_trusted_issuers_at, Credential, Pass,
+        UnverifiedPass, VerifiedPass,
+    },
+    payload::{
+        cose::signature::{verify::VerifyingKey, SignatureAlgorithm, NZCP_ALGORITHMS},
+        cwt::validation::{AppliedLeeway, Leeway},
+    },
+    report::{Check, CheckResult, CheckStatus, VerificationReport},
+    verifier::{Verifier, VerifierBuilder},
 };
 
+mod batch;
+mod clock;
 mod decentralised_identifier;
 pub mod error;
+mod issue;
+pub mod message;
 mod pass;
-mod payload;
+pub mod payload;
+mod report;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+mod verifier;
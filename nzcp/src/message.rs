@@ -0,0 +1,229 @@
+//! Short, user-facing messages for the outcome of verifying a pass, suitable for showing on a scanner screen.
+//!
+//! ```ignore
+//! use nzcp::message::{English, Message, TeReoMaori};
+//!
+//! let message = match verifier.verify::<PublicCovidPass>(barcode).await {
+//!     Ok(verified) => Message::from(&verified),
+//!     Err(error) => Message::from(&error),
+//! };
+//! println!("{}\n{}", message.localize(&English), message.localize(&TeReoMaori));
+//! ```
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use chrono_tz::{Pacific::Auckland, Tz};
+
+use crate::{
+    error::{CoseVerificationError, CwtValidationError, ErrorCategory, NzcpError},
+    pass::VerifiedPass,
+};
+
+/// What to tell the person scanning a pass, independent of language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// The pass is valid until the expiry date.
+    Valid { expiry: DateTime<Utc> },
+    /// The pass expired at the expiry date.
+    Expired { expiry: DateTime<Utc> },
+    /// The pass isn't active until the not before date.
+    NotYetValid { not_before: DateTime<Utc> },
+    /// The pass isn't signed by a trusted issuer.
+    Untrusted,
+    /// The barcode isn't a valid pass.
+    Invalid,
+    /// The pass couldn't be checked (e.g. the issuer's keys couldn't be fetched), so scanning again may work.
+    Unavailable,
+}
+
+impl Message {
+    /// The message in the catalogue's language.
+    pub fn localize(&self, catalogue: &dyn MessageCatalogue) -> String {
+        catalogue.message(self)
+    }
+
+    /// Whether the pass was accepted.
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Message::Valid { .. })
+    }
+}
+
+impl<P> From<&VerifiedPass<P>> for Message {
+    fn from(verified: &VerifiedPass<P>) -> Self {
+        Message::Valid {
+            expiry: verified.expiry,
+        }
+    }
+}
+
+impl From<&NzcpError> for Message {
+    fn from(error: &NzcpError) -> Self {
+        let cwt_error = match error {
+            NzcpError::InvalidCWT(error) => Some(error),
+            NzcpError::InvalidSignature(CoseVerificationError::CwtValidation(error)) => Some(error),
+            _ => None,
+        };
+
+        match cwt_error {
+            Some(CwtValidationError::Expired(expiry)) => Message::Expired { expiry: *expiry },
+            Some(CwtValidationError::NotYetActive(not_before)) => Message::NotYetValid {
+                not_before: *not_before,
+            },
+            _ => match error.category() {
+                ErrorCategory::Untrusted => Message::Untrusted,
+                ErrorCategory::Network => Message::Unavailable,
+                ErrorCategory::Malformed
+                | ErrorCategory::Expired
+                | ErrorCategory::NotYetValid
+                | ErrorCategory::Unsupported => Message::Invalid,
+            },
+        }
+    }
+}
+
+/// The messages in one language.
+///
+/// Implement this to add a language, using [`nz_local_time`] to show dates in the time passes are checked in.
+pub trait MessageCatalogue: Send + Sync {
+    fn message(&self, message: &Message) -> String;
+}
+
+/// Messages in English, e.g. "Pass expired on 30 March 2022 at 4:25 pm".
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct English;
+
+impl MessageCatalogue for English {
+    fn message(&self, message: &Message) -> String {
+        let date = |date: &DateTime<Utc>| nz_local_time(*date).format("%-d %B %Y at %-I:%M %P").to_string();
+
+        match message {
+            Message::Valid { expiry } => format!("Pass verified, valid until {}", date(expiry)),
+            Message::Expired { expiry } => format!("Pass expired on {}", date(expiry)),
+            Message::NotYetValid { not_before } => format!("Pass not valid until {}", date(not_before)),
+            Message::Untrusted => String::from("Pass not issued by a trusted issuer"),
+            Message::Invalid => String::from("Not a valid NZ COVID Pass"),
+            Message::Unavailable => String::from("Couldn't check the pass, please scan again"),
+        }
+    }
+}
+
+/// Messages in te reo Māori, e.g. "Kua pau te pāhi i te 30 o Poutūterangi 2022, 16:25".
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TeReoMaori;
+
+/// The months of the year in te reo Māori.
+const MAORI_MONTHS: [&str; 12] = [
+    "Kohitātea",
+    "Huitanguru",
+    "Poutūterangi",
+    "Paengawhāwhā",
+    "Haratua",
+    "Pipiri",
+    "Hōngongoi",
+    "Here-turi-kōkā",
+    "Mahuru",
+    "Whiringa-ā-nuku",
+    "Whiringa-ā-rangi",
+    "Hakihea",
+];
+
+impl MessageCatalogue for TeReoMaori {
+    fn message(&self, message: &Message) -> String {
+        let date = |date: &DateTime<Utc>| {
+            let date = nz_local_time(*date);
+            format!(
+                "{} o {} {}, {}:{:02}",
+                date.day(),
+                MAORI_MONTHS[date.month0() as usize],
+                date.year(),
+                date.hour(),
+                date.minute()
+            )
+        };
+
+        match message {
+            Message::Valid { expiry } => format!("Kua manatokona te pāhi, ka mana tonu tae noa ki te {}", date(expiry)),
+            Message::Expired { expiry } => format!("Kua pau te pāhi i te {}", date(expiry)),
+            Message::NotYetValid { not_before } => {
+                format!("Kāore anō te pāhi kia whai mana, tae noa ki te {}", date(not_before))
+            }
+            Message::Untrusted => String::from("Kāore te pāhi i tukuna e tētahi kaituku e whakawhirinakitia ana"),
+            Message::Invalid => String::from("Ehara tēnei i te Pāhi COVID o Aotearoa tika"),
+            Message::Unavailable => String::from("Kāore i taea te takitaki i te pāhi, me matawai anō"),
+        }
+    }
+}
+
+/// The date in New Zealand local time (`Pacific/Auckland`, including daylight saving).
+pub fn nz_local_time(date: DateTime<Utc>) -> DateTime<Tz> {
+    date.with_timezone(&Auckland)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::error::{Claim, DecentralizedIdentifierError, PayloadError};
+
+    #[test]
+    fn messages_from_errors() {
+        let expiry = Utc.timestamp(1635278731, 0);
+        assert_eq!(
+            Message::from(&NzcpError::InvalidCWT(CwtValidationError::Expired(expiry))),
+            Message::Expired { expiry }
+        );
+        assert_eq!(
+            Message::from(&NzcpError::InvalidSignature(CoseVerificationError::UntrustedIssuer(
+                String::from("did:web:example.com")
+            ))),
+            Message::Untrusted
+        );
+        assert_eq!(
+            Message::from(&NzcpError::InvalidSignature(
+                CoseVerificationError::DecentralizedIdentifierResolution(
+                    DecentralizedIdentifierError::ResolutionError(String::from("timed out"))
+                )
+            )),
+            Message::Unavailable
+        );
+        assert_eq!(
+            Message::from(&NzcpError::InvalidPayload(PayloadError::MissingClaim(
+                Claim::VerifiableCredential
+            ))),
+            Message::Invalid
+        );
+    }
+
+    #[test]
+    fn english() {
+        // 2021-10-26T20:05:31Z, during daylight saving (NZDT, +13:00)
+        let expiry = Utc.timestamp(1635278731, 0);
+        assert_eq!(
+            Message::Expired { expiry }.localize(&English),
+            "Pass expired on 27 October 2021 at 9:05 am"
+        );
+
+        // 2031-11-02T20:05:30Z, during daylight saving
+        let expiry = Utc.timestamp(1951416330, 0);
+        assert_eq!(
+            Message::Valid { expiry }.localize(&English),
+            "Pass verified, valid until 3 November 2031 at 9:05 am"
+        );
+    }
+
+    #[test]
+    fn te_reo_maori() {
+        let expiry = Utc.timestamp(1635278731, 0);
+        assert_eq!(
+            Message::Expired { expiry }.localize(&TeReoMaori),
+            "Kua pau te pāhi i te 27 o Whiringa-ā-nuku 2021, 9:05"
+        );
+
+        // 2021-06-30T12:00:00Z, outside daylight saving (NZST, +12:00)
+        let not_before = Utc.timestamp(1625054400, 0);
+        assert_eq!(
+            Message::NotYetValid { not_before }.localize(&TeReoMaori),
+            "Kāore anō te pāhi kia whai mana, tae noa ki te 1 o Hōngongoi 2021, 0:00"
+        );
+    }
+}
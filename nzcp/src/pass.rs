@@ -1,13 +1,34 @@
+use std::sync::OnceLock;
+
 use serde::de::DeserializeOwned;
 
+pub use self::{unverified_pass::UnverifiedPass, verified_pass::VerifiedPass};
 use crate::{
-    decentralised_identifier::DecentralizedIdentifier,
+    clock::{Clock, SystemClock},
+    decentralised_identifier::{
+        resolver::{BlockingKeyResolver, KeyResolver},
+        trust_store::TrustStore,
+        DecentralizedIdentifier,
+    },
     error::NzcpError,
-    payload::{barcode::QrBarcode, cose::CoseStructure},
+    payload::{
+        barcode::QrBarcode,
+        cose::{
+            signature::{SignatureAlgorithm, NZCP_ALGORITHMS},
+            CoseStructure,
+        },
+        cwt::validation::{validate_context, validate_type, AppliedLeeway, CwtValidationError, Leeway},
+    },
+    verifier::Verifier,
 };
 
+pub(crate) mod any_pass;
 pub(crate) mod public_covid_pass;
+pub(crate) mod raw_pass;
+pub(crate) mod unverified_pass;
+pub(crate) mod verified_pass;
 
+/// A single NZCP pass type, which is the credential subject of the verifiable credential.
 pub trait Pass: DeserializeOwned {
     /// The type ID of the pass, given in `vc.type[1]`. (e.g. 'PublicCovidPass')
     const CREDENTIAL_TYPE: &'static str;
@@ -16,37 +37,358 @@ pub trait Pass: DeserializeOwned {
     const CONTEXT_URL: &'static str;
 }
 
-const MINISTRY_OF_HEALTH_ISSUER: DecentralizedIdentifier<'static> =
+/// What a pass can be verified as: either a single [`Pass`] type, or one of several pass types chosen between by the
+/// credential type (`vc.type[1]`), such as [`AnyPass`](crate::AnyPass).
+///
+/// Implemented for every `Pass`. Implement it for your own enum to verify a different set of pass types.
+pub trait Credential: DeserializeOwned {
+    /// Check the credential type (`vc.type`) is supported.
+    fn validate_type(credential_type: (&str, &str)) -> Result<(), CwtValidationError>;
+
+    /// Check the JSON-LD contexts (`vc.@context`) are those for the credential type (`vc.type[1]`).
+    fn validate_context(context: &[&str], credential_type: &str) -> Result<(), CwtValidationError>;
+
+    /// Convert the deserialized credential subject for the JSON-LD contexts and credential type (`vc.type[1]`), once
+    /// validated.
+    ///
+    /// The credential type isn't known while the subject is deserialized, so a credential choosing between pass types
+    /// should deserialize the subject as is (e.g. as a CBOR `Value`) and choose here. Returns the subject unchanged by
+    /// default.
+    fn for_credential(self, context: &[&str], credential_type: &str) -> Result<Self, CwtValidationError> {
+        let _ = (context, credential_type);
+        Ok(self)
+    }
+}
+
+impl<P: Pass> Credential for P {
+    fn validate_type(credential_type: (&str, &str)) -> Result<(), CwtValidationError> {
+        validate_type::<P>(credential_type)
+    }
+
+    fn validate_context(context: &[&str], _credential_type: &str) -> Result<(), CwtValidationError> {
+        validate_context::<P>(context)
+    }
+}
+
+pub(crate) const MINISTRY_OF_HEALTH_ISSUER: DecentralizedIdentifier<'static> =
     DecentralizedIdentifier::Web("nzcp.identity.health.nz");
 
+/// The bundled MoH DID document, parsed on first use rather than on every blocking verification.
+static MINISTRY_OF_HEALTH_TRUST_STORE: OnceLock<TrustStore> = OnceLock::new();
+
 /// Verify a pass barcode URI (from a scanned QR code), returning the pass if verified or failing if not.
 ///
 /// A valid URI starts with `NZCP:/1/` followed by a base 32 string.
 ///
+/// Trusts only the MoH `nzcp.identity.health.nz` issuer. Use a [`Verifier`] to verify many passes with the same
+/// configuration.
+pub async fn verify_pass_uri<P: Credential>(uri: &str) -> Result<P, NzcpError> {
+    let verified = Verifier::default().verify(uri).await?;
+    Ok(verified.pass)
+}
+
+/// Verify a pass barcode URI as at the time given by the clock, returning the pass if verified or failing if not.
+///
+/// Passing a fixed `DateTime<Utc>` answers whether the pass was valid at that time.
+///
 /// Trusts only the MoH `nzcp.identity.health.nz` issuer.
-pub async fn verify_pass_uri<P: Pass>(uri: &str) -> Result<P, NzcpError> {
-    verify_pass_uri_with_trusted_issuers(uri, &[MINISTRY_OF_HEALTH_ISSUER]).await
+pub async fn verify_pass_uri_at<P: Credential>(uri: &str, clock: impl Clock + 'static) -> Result<P, NzcpError> {
+    let verified = Verifier::builder().clock(clock).build().verify(uri).await?;
+    Ok(verified.pass)
 }
 
 /// Verify a pass barcode, returning the pass if verified or failing if not.
 ///
 /// Trusts only the provided issuer (should only be used for tests where the identifier is different).
 #[doc(hidden)]
-pub async fn verify_pass_uri_with_trusted_issuers<P: Pass>(
+pub async fn verify_pass_uri_with_trusted_issuers<P: Credential>(
+    barcode_str: &str,
+    trusted_issuers: &[DecentralizedIdentifier<'_>],
+) -> Result<P, NzcpError> {
+    verify_pass_uri_with_trusted_issuers_at(barcode_str, trusted_issuers, SystemClock).await
+}
+
+/// Verify a pass barcode as at the time given by the clock, returning the pass if verified or failing if not.
+///
+/// Trusts only the provided issuer (should only be used for tests where the identifier is different).
+#[doc(hidden)]
+pub async fn verify_pass_uri_with_trusted_issuers_at<P: Credential>(
     barcode_str: &str,
     trusted_issuers: &[DecentralizedIdentifier<'_>],
+    clock: impl Clock + 'static,
 ) -> Result<P, NzcpError> {
+    let verifier = Verifier::builder()
+        .trusted_issuers(trusted_issuers.iter().copied())
+        .clock(clock)
+        .build();
+    let verified = verifier.verify(barcode_str).await?;
+    Ok(verified.pass)
+}
+
+/// Verify a pass barcode URI, tolerating the given clock skew in the not before and expiry dates.
+///
+/// Returns the pass along with the leeway which was needed to accept it, if any.
+///
+/// Trusts only the MoH `nzcp.identity.health.nz` issuer.
+pub async fn verify_pass_uri_with_leeway<P: Credential>(
+    uri: &str,
+    leeway: Leeway,
+) -> Result<(P, AppliedLeeway), NzcpError> {
+    let verified = Verifier::builder().leeway(leeway).build().verify(uri).await?;
+    Ok((verified.pass, verified.applied_leeway))
+}
+
+/// Verify a pass barcode URI, resolving the issuer's verifying key with the given resolver (e.g. a `TrustStore` for
+/// offline verification, or a `KeyCache` to avoid resolving the DID document for every pass).
+///
+/// Trusts only the MoH `nzcp.identity.health.nz` issuer.
+pub async fn verify_pass_uri_with_resolver<P: Credential>(
+    uri: &str,
+    resolver: &impl KeyResolver,
+) -> Result<P, NzcpError> {
+    let verified = verify_pass(
+        uri,
+        &[MINISTRY_OF_HEALTH_ISSUER],
+        &NZCP_ALGORITHMS,
+        &SystemClock,
+        Leeway::default(),
+        resolver,
+    )
+    .await?;
+    Ok(verified.pass)
+}
+
+/// Verify a pass barcode URI synchronously, without an async runtime, returning the pass if verified or failing if
+/// not.
+///
+/// Trusts only the MoH `nzcp.identity.health.nz` issuer, using the DID document bundled with this crate.
+pub fn verify_pass_uri_blocking<P: Credential>(uri: &str) -> Result<P, NzcpError> {
+    let verified = verify_pass_blocking(
+        uri,
+        &[MINISTRY_OF_HEALTH_ISSUER],
+        &NZCP_ALGORITHMS,
+        &SystemClock,
+        Leeway::default(),
+        MINISTRY_OF_HEALTH_TRUST_STORE.get_or_init(TrustStore::ministry_of_health),
+    )?;
+    Ok(verified.pass)
+}
+
+/// **UNVERIFIED**: decode a pass barcode URI without verifying its signature or validating its claims, e.g. to see
+/// why a pass is rejected. Fails only if the barcode or its structure is invalid.
+///
+/// No DID documents are resolved, so this works offline. Never accept a pass because it decodes; use
+/// [`verify_pass_uri`] or a [`Verifier`].
+pub fn decode_pass_uri<P: Credential>(uri: &str) -> Result<UnverifiedPass<P>, NzcpError> {
+    let barcode: QrBarcode = uri.parse()?;
+    let cose = CoseStructure::<P>::from_slice(&barcode.cbor)?;
+
+    Ok(UnverifiedPass::new(cose)?)
+}
+
+pub(crate) async fn verify_pass<P: Credential>(
+    barcode_str: &str,
+    trusted_issuers: &[DecentralizedIdentifier<'_>],
+    allowed_algorithms: &[SignatureAlgorithm],
+    clock: &dyn Clock,
+    leeway: Leeway,
+    resolver: &dyn KeyResolver,
+) -> Result<VerifiedPass<P>, NzcpError> {
     // extract the decoded data from the barcode string
     let barcode: QrBarcode = barcode_str.parse()?;
 
     // deserialize the barcode data to COSE
-    let cose: CoseStructure<'_, P> = serde_cbor::from_slice(&barcode.0)?;
+    let cose = CoseStructure::<P>::from_slice(&barcode.cbor)?;
+
+    // check the signature algorithm is allowed before resolving the key
+    cose.verify_algorithm(allowed_algorithms)?;
 
     // verify the COST signature and get the inner CWT
-    let cwt = cose.verified_claims(trusted_issuers).await?;
+    let kid = cose.kid();
+    let cwt = cose.verified_claims(trusted_issuers, resolver).await?;
 
     // validate the CWT and get the inner pass
-    let pass = cwt.validated_credential_subject()?;
+    let verified_pass = cwt.validated_pass(kid, clock.now(), leeway)?;
+
+    Ok(verified_pass)
+}
+
+pub(crate) fn verify_pass_blocking<P: Credential>(
+    barcode_str: &str,
+    trusted_issuers: &[DecentralizedIdentifier<'_>],
+    allowed_algorithms: &[SignatureAlgorithm],
+    clock: &dyn Clock,
+    leeway: Leeway,
+    resolver: &dyn BlockingKeyResolver,
+) -> Result<VerifiedPass<P>, NzcpError> {
+    let barcode: QrBarcode = barcode_str.parse()?;
+    let cose = CoseStructure::<P>::from_slice(&barcode.cbor)?;
+    cose.verify_algorithm(allowed_algorithms)?;
+    let kid = cose.kid();
+    let cwt = cose.verified_claims_blocking(trusted_issuers, resolver)?;
+    let verified_pass = cwt.validated_pass(kid, clock.now(), leeway)?;
+
+    Ok(verified_pass)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{
+        error::{CoseVerificationError, DecentralizedIdentifierError, QrBarcodeError},
+        PublicCovidPass, TrustStore, VerifyingKey,
+    };
+
+    const EXAMPLE_ISSUER: DecentralizedIdentifier<'static> = DecentralizedIdentifier::Web("nzcp.covid19.health.nz");
+
+    #[tokio::test]
+    async fn verify_with_trust_store() {
+        let mut trust_store = TrustStore::new();
+        trust_store
+            .add_document_json(include_str!("../tests/did/nzcp.covid19.health.nz.json"))
+            .unwrap();
+
+        // https://nzcp.covid19.health.nz/#valid-worked-example
+        let barcode = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX";
+
+        let verified: VerifiedPass<PublicCovidPass> = verify_pass(
+            barcode,
+            &[EXAMPLE_ISSUER],
+            &NZCP_ALGORITHMS,
+            &SystemClock,
+            Leeway::default(),
+            &trust_store,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(verified.pass.given_name, "Jack");
+    }
+
+    #[test]
+    fn verify_blocking() {
+        let barcode = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX";
+
+        let mut trust_store = TrustStore::new();
+        trust_store
+            .add_document_json(include_str!("../tests/did/nzcp.covid19.health.nz.json"))
+            .unwrap();
+
+        let verified: VerifiedPass<PublicCovidPass> = verify_pass_blocking(
+            barcode,
+            &[EXAMPLE_ISSUER],
+            &NZCP_ALGORITHMS,
+            &SystemClock,
+            Leeway::default(),
+            &trust_store,
+        )
+        .unwrap();
+        assert_eq!(verified.pass.given_name, "Jack");
+
+        // the bundled MoH document doesn't contain the example issuer
+        assert!(verify_pass_uri_blocking::<PublicCovidPass>(barcode).is_err());
+    }
+
+    #[tokio::test]
+    async fn trust_store_rejects_unknown_issuer() {
+        let barcode = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX";
+
+        let error = verify_pass::<PublicCovidPass>(
+            barcode,
+            &[EXAMPLE_ISSUER],
+            &NZCP_ALGORITHMS,
+            &SystemClock,
+            Leeway::default(),
+            &TrustStore::ministry_of_health(),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            error,
+            NzcpError::InvalidSignature(CoseVerificationError::DecentralizedIdentifierResolution(
+                DecentralizedIdentifierError::MissingDocument(String::from("did:web:nzcp.covid19.health.nz"))
+            ))
+        );
+    }
+
+    /// Resolves every key to the same verifying key, recording the keys it was asked for.
+    struct FixedKeyResolver {
+        verifying_key: VerifyingKey,
+        requested: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl KeyResolver for FixedKeyResolver {
+        async fn resolve_verifying_key(
+            &self,
+            issuer: DecentralizedIdentifier<'_>,
+            kid: &str,
+        ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+            self.requested.lock().unwrap().push((issuer.did(), kid.to_owned()));
+            Ok(self.verifying_key.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_with_custom_resolver() {
+        let mut trust_store = TrustStore::new();
+        trust_store
+            .add_document_json(include_str!("../tests/did/nzcp.covid19.health.nz.json"))
+            .unwrap();
+        let resolver = FixedKeyResolver {
+            verifying_key: trust_store.verifying_key(EXAMPLE_ISSUER, "key-1").unwrap(),
+            requested: Mutex::default(),
+        };
+
+        // https://nzcp.covid19.health.nz/#valid-worked-example
+        let barcode = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX";
+
+        let verified: VerifiedPass<PublicCovidPass> = verify_pass(
+            barcode,
+            &[EXAMPLE_ISSUER],
+            &NZCP_ALGORITHMS,
+            &SystemClock,
+            Leeway::default(),
+            &resolver,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(verified.pass.given_name, "Jack");
+        assert_eq!(
+            *resolver.requested.lock().unwrap(),
+            vec![(String::from("did:web:nzcp.covid19.health.nz"), String::from("key-1"))]
+        );
+    }
+
+    #[test]
+    fn decode_without_verifying() {
+        // https://nzcp.covid19.health.nz/#modified-signature, which fails verification but still decodes
+        let barcode = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIAAAAAAAAAAAAAAAAC63WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX";
+
+        let pass = decode_pass_uri::<PublicCovidPass>(barcode).unwrap();
+        assert_eq!(pass.unverified_protected_headers().kid, "key-1");
+
+        let claims = pass.unverified_cwt_claims();
+        assert_eq!(claims.issuer(), EXAMPLE_ISSUER);
+        assert_eq!(
+            claims.cwt_token_id().to_urn().to_string(),
+            "urn:uuid:60a4f54d-4e30-4332-be33-ad78b1eafa4b"
+        );
+        assert_eq!(
+            claims.verifiable_credential().credential_type(),
+            ("VerifiableCredential", "PublicCovidPass")
+        );
+        assert_eq!(pass.unverified_credential_subject().given_name, "Jack");
 
-    Ok(pass)
+        assert_eq!(
+            decode_pass_uri::<PublicCovidPass>("NZCP:/2/AAAA").unwrap_err(),
+            NzcpError::QrBarcode(QrBarcodeError::UnsupportedVersion(2))
+        );
+    }
 }
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Deserializer};
+use serde_cbor::Value;
+
+use super::{public_covid_pass::PublicCovidPass, Credential, Pass};
+use crate::payload::cwt::validation::{
+    validate_mandatory_context, validate_mandatory_type, CwtValidationError, MANDATAORY_CONTEXT_URL,
+};
+
+/// A pass of whichever NZCP pass type was scanned, chosen by the credential type (`vc.type[1]`).
+///
+/// Passes of a type this crate doesn't know are still verified, as [`AnyPass::Unknown`], so they can be reported
+/// rather than rejected as malformed.
+///
+/// The known pass types are fixed by this crate. To verify a different set of pass types, implement [`Credential`] for
+/// your own enum in the same way, choosing the pass type in [`Credential::for_credential`].
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AnyPass {
+    PublicCovidPass(PublicCovidPass),
+    /// A pass type this crate doesn't know, with its credential subject as is.
+    ///
+    /// Deserializing the credential subject alone gives this with an empty credential type, until
+    /// [`Credential::for_credential`] chooses the pass type. Verifying and decoding passes always choose it.
+    Unknown {
+        credential_type: String,
+        credential_subject: Value,
+    },
+}
+
+/// A pass type [`AnyPass`] verifies as its typed subject.
+struct PassType {
+    credential_type: &'static str,
+    context_url: &'static str,
+    deserialize: fn(Value) -> Result<AnyPass, serde_cbor::Error>,
+}
+
+/// The pass types this crate knows. Add a pass type by adding an `AnyPass` variant and an entry here.
+static PASS_TYPES: [PassType; 1] = [PassType {
+    credential_type: PublicCovidPass::CREDENTIAL_TYPE,
+    context_url: PublicCovidPass::CONTEXT_URL,
+    deserialize: |subject| serde_cbor::value::from_value(subject).map(AnyPass::PublicCovidPass),
+}];
+
+fn pass_type(credential_type: &str) -> Option<&'static PassType> {
+    PASS_TYPES
+        .iter()
+        .find(|pass_type| pass_type.credential_type == credential_type)
+}
+
+impl AnyPass {
+    /// The credential types which are verified as their typed subject (e.g. `PublicCovidPass`).
+    pub fn known_credential_types() -> impl Iterator<Item = &'static str> {
+        PASS_TYPES.iter().map(|pass_type| pass_type.credential_type)
+    }
+
+    /// The credential type of the pass, given in `vc.type[1]`.
+    pub fn credential_type(&self) -> &str {
+        match self {
+            AnyPass::PublicCovidPass(_) => PublicCovidPass::CREDENTIAL_TYPE,
+            AnyPass::Unknown { credential_type, .. } => credential_type,
+        }
+    }
+
+    /// Whether the pass type isn't one this crate knows.
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, AnyPass::Unknown { .. })
+    }
+}
+
+/// The credential type isn't known until the whole credential has been deserialized, so the subject is kept as is,
+/// as an [`AnyPass::Unknown`] with an empty credential type, until [`Credential::for_credential`].
+impl<'de> Deserialize<'de> for AnyPass {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(AnyPass::Unknown {
+            credential_type: String::new(),
+            credential_subject: Value::deserialize(deserializer)?,
+        })
+    }
+}
+
+impl Credential for AnyPass {
+    fn validate_type(credential_type: (&str, &str)) -> Result<(), CwtValidationError> {
+        validate_mandatory_type(credential_type)
+    }
+
+    /// Unknown pass types only need the mandatory W3C context, as their context URL isn't known.
+    fn validate_context(context: &[&str], credential_type: &str) -> Result<(), CwtValidationError> {
+        match pass_type(credential_type) {
+            Some(pass_type) if context.get(0..2) != Some(&[MANDATAORY_CONTEXT_URL, pass_type.context_url]) => Err(
+                CwtValidationError::InvalidContext(context.iter().map(|str| String::from(*str)).collect()),
+            ),
+            _ => validate_mandatory_context(context),
+        }
+    }
+
+    fn for_credential(self, _context: &[&str], credential_type: &str) -> Result<Self, CwtValidationError> {
+        let credential_subject = match self {
+            AnyPass::Unknown { credential_subject, .. } => credential_subject,
+            pass => return Ok(pass),
+        };
+
+        match pass_type(credential_type) {
+            Some(pass_type) => (pass_type.deserialize)(credential_subject).map_err(|error| {
+                CwtValidationError::InvalidCredentialSubject(credential_type.to_owned(), error.to_string())
+            }),
+            None => Ok(AnyPass::Unknown {
+                credential_type: credential_type.to_owned(),
+                credential_subject,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::payload::cwt::validation::MANDATAORY_TYPE;
+
+    fn subject() -> Value {
+        serde_cbor::value::to_value(PublicCovidPass {
+            given_name: String::from("Jack"),
+            family_name: Some(String::from("Sparrow")),
+            date_of_birth: NaiveDate::from_ymd(1960, 4, 16),
+        })
+        .unwrap()
+    }
+
+    fn deserialized(credential_subject: Value) -> AnyPass {
+        serde_cbor::value::from_value(credential_subject).unwrap()
+    }
+
+    #[test]
+    fn dispatch_on_credential_type() {
+        let pass = deserialized(subject()).for_credential(&[], "PublicCovidPass").unwrap();
+        assert!(matches!(&pass, AnyPass::PublicCovidPass(pass) if pass.given_name == "Jack"));
+        assert_eq!(pass.credential_type(), "PublicCovidPass");
+
+        let pass = deserialized(subject()).for_credential(&[], "TravelPass").unwrap();
+        assert!(pass.is_unknown());
+        assert_eq!(pass.credential_type(), "TravelPass");
+
+        assert_eq!(
+            deserialized(Value::Text(String::from("Jack")))
+                .for_credential(&[], "PublicCovidPass")
+                .unwrap_err()
+                .code(),
+            crate::error::ErrorCode::InvalidCredentialSubject
+        );
+    }
+
+    #[test]
+    fn validate_credential() {
+        let context = [MANDATAORY_CONTEXT_URL, PublicCovidPass::CONTEXT_URL];
+        assert!(AnyPass::validate_context(&context, "PublicCovidPass").is_ok());
+        assert!(AnyPass::validate_context(&context[..1], "PublicCovidPass").is_err());
+        assert!(AnyPass::validate_context(&context[..1], "TravelPass").is_ok());
+        assert!(AnyPass::validate_context(&[], "TravelPass").is_err());
+
+        assert!(AnyPass::validate_type((MANDATAORY_TYPE, "TravelPass")).is_ok());
+        assert!(AnyPass::validate_type(("Credential", "PublicCovidPass")).is_err());
+    }
+}
@@ -5,6 +5,7 @@ use thiserror::Error;
 use super::Pass;
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum PublicCovidPassError {
     #[error("The given date of birth was invalid.")]
     InvalidDateOfBirth,
@@ -18,7 +19,7 @@ pub struct PublicCovidPass {
     pub given_name: String,
 
     /// Family name(s) of the subject of the pass.
-    #[serde(rename = "familyName")]
+    #[serde(rename = "familyName", skip_serializing_if = "Option::is_none")]
     pub family_name: Option<String>,
 
     /// Date of birth of the subject of the pass.
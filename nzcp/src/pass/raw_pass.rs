@@ -0,0 +1,60 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_cbor::Value;
+
+use super::Credential;
+use crate::payload::cwt::validation::{validate_mandatory_context, validate_mandatory_type, CwtValidationError};
+
+/// A pass of any type, with its credential subject as is, so tooling can verify the signature and show whatever fields
+/// a pass has (including pass types this crate doesn't model).
+///
+/// Only the W3C VC context and `VerifiableCredential` type are validated, so check the credential type and contexts
+/// before relying on the subject's contents.
+///
+/// Serializes as the credential's `@context`, `type` and `credentialSubject` (e.g. to show it as JSON).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RawPass {
+    /// The JSON-LD contexts (`vc.@context`), e.g. `["https://www.w3.org/2018/credentials/v1", ...]`.
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+
+    /// The declared pass type, given in `vc.type[1]` (e.g. `PublicCovidPass`).
+    #[serde(rename = "type")]
+    pub credential_type: String,
+
+    /// The credential subject (`vc.credentialSubject`), as is.
+    #[serde(rename = "credentialSubject")]
+    pub credential_subject: Value,
+}
+
+/// The contexts and credential type aren't known until the whole credential has been deserialized, so they are filled
+/// in by [`Credential::for_credential`].
+impl<'de> Deserialize<'de> for RawPass {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(RawPass {
+            context: Vec::new(),
+            credential_type: String::new(),
+            credential_subject: Value::deserialize(deserializer)?,
+        })
+    }
+}
+
+impl Credential for RawPass {
+    fn validate_type(credential_type: (&str, &str)) -> Result<(), CwtValidationError> {
+        validate_mandatory_type(credential_type)
+    }
+
+    fn validate_context(context: &[&str], _credential_type: &str) -> Result<(), CwtValidationError> {
+        validate_mandatory_context(context)
+    }
+
+    fn for_credential(self, context: &[&str], credential_type: &str) -> Result<Self, CwtValidationError> {
+        Ok(RawPass {
+            context: context.iter().map(|context| String::from(*context)).collect(),
+            credential_type: credential_type.to_owned(),
+            ..self
+        })
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::Credential;
+use crate::{
+    decentralised_identifier::DecentralizedIdentifier,
+    payload::{
+        cose::{protected_headers::ProtectedHeaders, signature::SignatureAlgorithm, CoseStructure},
+        cwt::{validation::CwtValidationError, CwtClaims, VerifiableCredential},
+    },
+};
+
+/// **UNVERIFIED**: a pass decoded without verifying its signature or validating its claims, for diagnosing why a pass
+/// is rejected. Anyone can create a barcode which decodes to any contents, so never treat it as proof of anything.
+///
+/// Unlike a [`VerifiedPass`](crate::VerifiedPass) it has no public fields and can't be converted into one; verify the
+/// barcode to get a verified pass.
+pub struct UnverifiedPass<P> {
+    kid: String,
+    algorithm: SignatureAlgorithm,
+    cwt_token_id: Uuid,
+    issuer: String,
+    not_before: DateTime<Utc>,
+    expiry: DateTime<Utc>,
+    context: Vec<String>,
+    version: String,
+    credential_type: (String, String),
+    credential_subject: P,
+}
+
+impl<P: Credential> UnverifiedPass<P> {
+    /// Take the contents of the COSE structure, without verifying or validating them.
+    pub(crate) fn new(cose: CoseStructure<'_, P>) -> Result<Self, CwtValidationError> {
+        let kid = cose.kid().to_owned();
+        let algorithm = cose.protected_headers().algorithm;
+        let claims = cose.into_cwt_claims();
+        let credential = claims.verifiable_credential;
+
+        Ok(UnverifiedPass {
+            kid,
+            algorithm,
+            cwt_token_id: claims.cwt_token_id,
+            issuer: claims.issuer.did(),
+            not_before: claims.not_before,
+            expiry: claims.expiry,
+            context: credential
+                .context
+                .iter()
+                .map(|context| String::from(*context))
+                .collect(),
+            version: credential.version.to_owned(),
+            credential_type: (credential._type.0.to_owned(), credential._type.1.to_owned()),
+            credential_subject: credential
+                .credential_subject
+                .for_credential(&credential.context, credential._type.1)?,
+        })
+    }
+}
+
+impl<P> UnverifiedPass<P> {
+    /// **UNVERIFIED**: the protected headers, which name the key the pass claims to be signed with.
+    pub fn unverified_protected_headers(&self) -> ProtectedHeaders<'_> {
+        ProtectedHeaders {
+            kid: &self.kid,
+            algorithm: self.algorithm,
+        }
+    }
+
+    /// **UNVERIFIED**: the CWT claims, including the verifiable credential and its subject.
+    pub fn unverified_cwt_claims(&self) -> CwtClaims<'_, &P> {
+        CwtClaims {
+            cwt_token_id: self.cwt_token_id,
+            issuer: DecentralizedIdentifier::parse(&self.issuer).expect("decoded issuers are did:web DIDs"),
+            not_before: self.not_before,
+            expiry: self.expiry,
+            verifiable_credential: VerifiableCredential {
+                context: self.context.iter().map(String::as_str).collect(),
+                version: &self.version,
+                _type: (&self.credential_type.0, &self.credential_type.1),
+                credential_subject: &self.credential_subject,
+            },
+        }
+    }
+
+    /// **UNVERIFIED**: the credential subject, i.e. the pass.
+    pub fn unverified_credential_subject(&self) -> &P {
+        &self.credential_subject
+    }
+
+    /// **UNVERIFIED**: the credential subject, i.e. the pass.
+    pub fn into_unverified_credential_subject(self) -> P {
+        self.credential_subject
+    }
+}
+
+impl<P: fmt::Debug> fmt::Debug for UnverifiedPass<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnverifiedPass")
+            .field("protected_headers", &self.unverified_protected_headers())
+            .field("cwt_claims", &self.unverified_cwt_claims())
+            .finish()
+    }
+}
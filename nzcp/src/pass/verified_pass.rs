@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::payload::{barcode::Normalisation, cwt::validation::AppliedLeeway};
+
+/// A verified pass, along with the details of the CWT and COSE headers it was verified from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedPass<P> {
+    /// The credential subject of the pass.
+    pub pass: P,
+
+    /// The CWT token ID (`jti` claim) uniquely identifying the pass.
+    pub cwt_token_id: Uuid,
+
+    /// The DID of the issuer which signed the pass (e.g. `did:web:nzcp.identity.health.nz`).
+    pub issuer: String,
+
+    /// The key ID (`kid` header) of the issuer's key which signed the pass.
+    pub kid: String,
+
+    /// The date the pass is active from.
+    pub not_before: DateTime<Utc>,
+
+    /// The date the pass expires.
+    pub expiry: DateTime<Utc>,
+
+    /// The version of the verifiable credential (e.g. `1.0.0`).
+    pub version: String,
+
+    /// The leeway which was needed to accept the pass, if any.
+    pub applied_leeway: AppliedLeeway,
+
+    /// The changes which were needed to make the barcode spec-exact, if the verifier accepts lenient barcodes.
+    pub normalisations: Vec<Normalisation>,
+}
+
+impl<P> VerifiedPass<P> {
+    /// The CWT token ID in its `urn:uuid:` form, as used by the MoH for revocation and audit.
+    pub fn jti(&self) -> String {
+        self.cwt_token_id.to_urn().to_string()
+    }
+
+    /// The absolute key reference of the key which signed the pass (e.g. `did:web:nzcp.identity.health.nz#z12Kf7UQ`).
+    pub fn key_reference(&self) -> String {
+        format!("{}#{}", self.issuer, self.kid)
+    }
+
+    pub fn into_pass(self) -> P {
+        self.pass
+    }
+}
@@ -1,3 +1,16 @@
+//! The layers of a pass barcode (the QR barcode, COSE structure and CWT claims), which can be decoded without
+//! verification to diagnose rejected passes.
+//!
+//! ```ignore
+//! use nzcp::payload::{barcode::QrBarcode, cose::CoseStructure};
+//!
+//! let barcode: QrBarcode = uri.parse()?;
+//! let cose: CoseStructure<'_, serde_cbor::Value> = CoseStructure::from_slice(&barcode.cbor)?;
+//! println!("signed with {}", cose.protected_headers().kid);
+//! ```
+
 pub mod barcode;
 pub mod cose;
 pub mod cwt;
+pub mod error;
+pub mod version;
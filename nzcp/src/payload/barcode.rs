@@ -1,29 +1,121 @@
-use std::str::FromStr;
+use std::{borrow::Cow, fmt, str::FromStr};
 
 use base32::Alphabet::RFC4648;
+use serde::Serialize;
 use thiserror::Error;
 
+use super::version::{parse_version_number, SUPPORTED_BARCODE_VERSIONS};
+
+/// Why a barcode was rejected. Positions are character offsets into the barcode.
 #[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum QrBarcodeError {
-    #[error("The payload of the QR Code MUST be base32 encoded")]
-    InvalidBase32,
-    #[error("The version-identifier portion of the payload for the specification MUST be 1")]
-    InvalidVersion,
+    #[error("The payload of the QR Code MUST be base32 encoded: invalid character {1:?} at position {0}")]
+    InvalidBase32Character(usize, char),
+    #[error("The payload of the QR Code MUST be base32 encoded: {0} characters isn't a valid base32 length")]
+    InvalidBase32Length(usize),
+    #[error("The base32 payload of the QR Code MUST NOT be padded: `=` at position {0}")]
+    Base32Padding(usize),
+    #[error("The QR Code MUST be upper case: lower case character at position {0}")]
+    Lowercase(usize),
+    #[error("The QR Code MUST NOT contain whitespace: whitespace at position {0}")]
+    Whitespace(usize),
+    #[error("The version-identifier portion of the payload MUST be a number followed by `/`")]
+    MalformedVersion,
+    #[error("The version-identifier {0} isn't supported, it may be a newer version of the specification")]
+    UnsupportedVersion(u32),
     #[error("The payload of the QR Code MUST begin with the prefix of `NZCP:/`")]
     MissingNzcpPrefix,
 }
 
+impl QrBarcodeError {
+    /// The position of the offending character, if there is one.
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            QrBarcodeError::InvalidBase32Character(position, _)
+            | QrBarcodeError::Base32Padding(position)
+            | QrBarcodeError::Lowercase(position)
+            | QrBarcodeError::Whitespace(position) => Some(*position),
+            QrBarcodeError::InvalidBase32Length(_)
+            | QrBarcodeError::MalformedVersion
+            | QrBarcodeError::UnsupportedVersion(_)
+            | QrBarcodeError::MissingNzcpPrefix => None,
+        }
+    }
+}
+
+/// A change made to a barcode by [`QrBarcode::normalise`] to make it spec-exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalisation {
+    /// Leading or trailing whitespace (e.g. a newline from a scanner) was removed.
+    Whitespace,
+    /// Lower case characters were converted to upper case.
+    Lowercase,
+    /// Base32 `=` padding was removed.
+    Base32Padding,
+}
+
+impl fmt::Display for Normalisation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Normalisation::Whitespace => "removed surrounding whitespace",
+            Normalisation::Lowercase => "converted to upper case",
+            Normalisation::Base32Padding => "removed base32 padding",
+        })
+    }
+}
+
+/// An error finding a pass QR code in an image.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QrImageError {
+    #[error("The image could not be read: {0}")]
+    InvalidImage(String),
+    #[error("No NZCP QR code was found in the image ({0} other QR codes were found)")]
+    NoNzcpQrCode(usize),
+}
+
+/// An error rendering a barcode as a QR code.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QrRenderError {
+    #[error("The barcode is too long to fit in a QR code ({0} characters)")]
+    TooLong(usize),
+    #[error(
+        "The QR code is too large to render with a {quiet_zone} module quiet zone and {module_size} pixel modules"
+    )]
+    TooLarge { quiet_zone: u32, module_size: u32 },
+}
+
 /// Expects a valid encoded pass from 2D barcode format encoded as:
 /// `NZCP:/<version-identifier>/<base32-encoded-CWT>`
 ///
-/// Holds the decoded CBOR bytes, which can then be deserialized.
+/// Holds the version identifier and the decoded CBOR bytes, which can then be deserialized. Only the versions in
+/// [`SUPPORTED_BARCODE_VERSIONS`] can be parsed.
 ///
 /// Implements `FromStr`, so use as follows:
 ///
 /// ```ignore
 /// let barcode: QrBarcode = "NZCP:/1/2KCEVIQ...".parse().unwrap;
 /// ```
-pub struct QrBarcode(pub Vec<u8>);
+///
+/// Implements `Display`, which encodes the CBOR bytes back into the barcode format.
+///
+/// Parsing is spec-exact. Use [`QrBarcode::parse_lenient`] to accept barcodes mangled by scanners or copy and paste.
+pub struct QrBarcode {
+    /// The version identifier, e.g. `1` for `NZCP:/1/...`.
+    pub version: u32,
+    /// The decoded CBOR bytes of the COSE structure.
+    pub cbor: Vec<u8>,
+}
+
+#[cfg(feature = "image")]
+pub mod image;
+#[cfg(feature = "render")]
+pub mod render;
+
+pub(crate) const NZCP_PREFIX: &str = "NZCP:/";
 
 impl FromStr for QrBarcode {
     type Err = QrBarcodeError;
@@ -31,14 +123,103 @@ impl FromStr for QrBarcode {
     fn from_str(string: &str) -> Result<Self, Self::Err> {
         use QrBarcodeError::*;
 
-        let base32_encoded_cwt = string
-            .strip_prefix("NZCP:/")
-            .ok_or(MissingNzcpPrefix)?
-            .strip_prefix("1/")
-            .ok_or(InvalidVersion)?;
+        if string.starts_with(char::is_whitespace) {
+            return Err(Whitespace(0));
+        }
+
+        let prefix = string.get(..NZCP_PREFIX.len()).ok_or(MissingNzcpPrefix)?;
+        if !prefix.eq_ignore_ascii_case(NZCP_PREFIX) {
+            return Err(MissingNzcpPrefix);
+        }
+        if let Some(position) = prefix.find(|character: char| character.is_ascii_lowercase()) {
+            return Err(Lowercase(position));
+        }
+
+        let (version, base32_encoded_cwt) = string[NZCP_PREFIX.len()..].split_once('/').ok_or(MalformedVersion)?;
+        let offset = NZCP_PREFIX.len() + version.len() + 1;
+        let version = parse_version_number(version).map_err(|_| MalformedVersion)?;
+        if !SUPPORTED_BARCODE_VERSIONS.contains(&version) {
+            return Err(UnsupportedVersion(version));
+        }
+        // checked after the prefix and version, so only whitespace after them is reported as whitespace
+        if string.ends_with(char::is_whitespace) {
+            return Err(Whitespace(string.trim_end().chars().count()));
+        }
+
+        // every character before an invalid one is ASCII, so byte offsets are character offsets
+        let unpadded = base32_encoded_cwt.trim_end_matches('=');
+        for (index, character) in unpadded.char_indices() {
+            match character {
+                'A'..='Z' | '2'..='7' => {}
+                'a'..='z' => return Err(Lowercase(offset + index)),
+                _ => return Err(InvalidBase32Character(offset + index, character)),
+            }
+        }
+        if unpadded.len() < base32_encoded_cwt.len() {
+            return Err(Base32Padding(offset + unpadded.len()));
+        }
+        // 5 bits per character, so these lengths leave whole characters of unused bits
+        if matches!(unpadded.len() % 8, 1 | 3 | 6) {
+            return Err(InvalidBase32Length(unpadded.len()));
+        }
+
+        let cbor = base32::decode(RFC4648 { padding: false }, unpadded).expect("validated base32");
+        Ok(QrBarcode { version, cbor })
+    }
+}
+
+impl QrBarcode {
+    /// A barcode of the latest supported version, e.g. for issuing a pass.
+    pub fn new(cbor: Vec<u8>) -> Self {
+        QrBarcode {
+            version: SUPPORTED_BARCODE_VERSIONS[SUPPORTED_BARCODE_VERSIONS.len() - 1],
+            cbor,
+        }
+    }
+
+    /// Fix the ways scanners and copy and paste commonly mangle barcodes: surrounding whitespace (e.g. a trailing
+    /// newline), lower case and base32 `=` padding. Returns the barcode as is if it needed no changes.
+    pub fn normalise(string: &str) -> (Cow<'_, str>, Vec<Normalisation>) {
+        let mut normalisations = Vec::new();
+        let mut normalised = Cow::Borrowed(string);
 
-        let cbor_array = base32::decode(RFC4648 { padding: false }, base32_encoded_cwt).ok_or(InvalidBase32)?;
-        Ok(QrBarcode(cbor_array))
+        let trimmed = string.trim();
+        if trimmed.len() < string.len() {
+            normalisations.push(Normalisation::Whitespace);
+            normalised = Cow::Borrowed(trimmed);
+        }
+
+        if normalised.contains(|character: char| character.is_ascii_lowercase()) {
+            normalisations.push(Normalisation::Lowercase);
+            normalised = Cow::Owned(normalised.to_ascii_uppercase());
+        }
+
+        let unpadded = normalised.trim_end_matches('=');
+        if unpadded.len() < normalised.len() {
+            normalisations.push(Normalisation::Base32Padding);
+            normalised = Cow::Owned(unpadded.to_owned());
+        }
+
+        (normalised, normalisations)
+    }
+
+    /// Parse a barcode after [normalising](QrBarcode::normalise) it, returning the changes which were needed.
+    pub fn parse_lenient(string: &str) -> Result<(Self, Vec<Normalisation>), QrBarcodeError> {
+        let (normalised, normalisations) = QrBarcode::normalise(string);
+
+        Ok((normalised.parse()?, normalisations))
+    }
+}
+
+impl fmt::Display for QrBarcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}/{}",
+            NZCP_PREFIX,
+            self.version,
+            base32::encode(RFC4648 { padding: false }, &self.cbor)
+        )
     }
 }
 
@@ -55,7 +236,7 @@ mod tests {
         let payload: QrBarcode = encoded.parse().unwrap();
 
         let mut hex_str = String::new();
-        for byte in payload.0 {
+        for byte in payload.cbor {
             write!(&mut hex_str, "{:02x}", byte).unwrap();
         }
 
@@ -64,4 +245,77 @@ mod tests {
             "d2844aa204456b65792d310126a059011fa501781e6469643a7765623a6e7a63702e636f76696431392e6865616c74682e6e7a051a61819a0a041a7450400a627663a46840636f6e7465787482782668747470733a2f2f7777772e77332e6f72672f323031382f63726564656e7469616c732f7631782a68747470733a2f2f6e7a63702e636f76696431392e6865616c74682e6e7a2f636f6e74657874732f76316776657273696f6e65312e302e306474797065827456657269666961626c6543726564656e7469616c6f5075626c6963436f766964506173737163726564656e7469616c5375626a656374a369676976656e4e616d65644a61636b6a66616d696c794e616d656753706172726f7763646f626a313936302d30342d3136075060a4f54d4e304332be33ad78b1eafa4b5840d2e07b1dd7263d833166bdbb4f1a093837a905d7eca2ee836b6b2ada23c23154fba88a529f675d6686ee632b09ec581ab08f72b458904bb3396d10fa66d11477"
         )
     }
+
+    #[test]
+    fn serialize_barcode() {
+        let encoded = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX";
+
+        let payload: QrBarcode = encoded.parse().unwrap();
+
+        assert_eq!(payload.to_string(), encoded)
+    }
+
+    #[test]
+    fn strict_errors() {
+        use QrBarcodeError::*;
+
+        let errors = [
+            ("https://example.com", MissingNzcpPrefix),
+            ("NZCP:", MissingNzcpPrefix),
+            ("NZCP:/2/2KCE", UnsupportedVersion(2)),
+            ("NZCP:/01/2KCE", MalformedVersion),
+            ("NZCP:/v1/2KCE", MalformedVersion),
+            ("NZCP:/2KCE", MalformedVersion),
+            (" NZCP:/1/2KCE", Whitespace(0)),
+            ("NZCP:/1/2KCE\r\n", Whitespace(12)),
+            ("hello world \n", MissingNzcpPrefix),
+            ("NZCP:/v1/2KCE \n", MalformedVersion),
+            ("nzcp:/1/2KCE", Lowercase(0)),
+            ("NZCP:/1/2KcE", Lowercase(10)),
+            ("NZCP:/1/2KCEVIQ=", Base32Padding(15)),
+            ("NZCP:/1/2K=CE", InvalidBase32Character(10, '=')),
+            ("NZCP:/1/2KC1", InvalidBase32Character(11, '1')),
+            ("NZCP:/1/2KCE VIQ", InvalidBase32Character(12, ' ')),
+            ("NZCP:/1/2KC", InvalidBase32Length(3)),
+        ];
+
+        for (barcode, error) in errors {
+            assert_eq!(barcode.parse::<QrBarcode>().err(), Some(error), "{}", barcode);
+        }
+    }
+
+    #[test]
+    fn normalise() {
+        assert_eq!(
+            QrBarcode::normalise("NZCP:/1/2KCE"),
+            (Cow::Borrowed("NZCP:/1/2KCE"), Vec::new())
+        );
+        assert_eq!(
+            QrBarcode::normalise("  nzcp:/1/2kceviq=\r\n"),
+            (
+                Cow::Owned(String::from("NZCP:/1/2KCEVIQ")),
+                vec![
+                    Normalisation::Whitespace,
+                    Normalisation::Lowercase,
+                    Normalisation::Base32Padding
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn parse_lenient() {
+        let encoded = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX";
+        let mangled = format!("{}======\n", encoded.to_lowercase());
+
+        assert!(mangled.parse::<QrBarcode>().is_err());
+        let (barcode, normalisations) = QrBarcode::parse_lenient(&mangled).unwrap();
+        assert_eq!(barcode.to_string(), encoded);
+        assert_eq!(normalisations.len(), 3);
+
+        assert_eq!(
+            QrBarcode::parse_lenient("NZCP:/1/2KC1").err(),
+            Some(QrBarcodeError::InvalidBase32Character(11, '1'))
+        );
+    }
 }
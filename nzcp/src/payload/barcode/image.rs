@@ -0,0 +1,154 @@
+//! Finding pass QR codes in photos and screenshots. Enabled by the `image` feature.
+
+use std::path::Path;
+
+use image::{imageops, DynamicImage, GrayImage};
+
+use super::{QrImageError, NZCP_PREFIX};
+
+/// Find the NZCP barcode URIs (`NZCP:/...`) in a PNG or JPEG image, in the order found.
+///
+/// QR codes are found at any rotation. If none are found the image is tried again with its contrast stretched (for
+/// washed out photos), then inverted (for dark mode screenshots). Fails if there are no NZCP QR codes, even if there
+/// are other QR codes.
+pub fn barcode_uris_from_image(bytes: &[u8]) -> Result<Vec<String>, QrImageError> {
+    let image = image::load_from_memory(bytes).map_err(|error| QrImageError::InvalidImage(error.to_string()))?;
+
+    barcode_uris_from_dynamic_image(&image)
+}
+
+/// Find the NZCP barcode URIs in a PNG or JPEG image file. See [`barcode_uris_from_image`].
+pub fn barcode_uris_from_image_file(path: impl AsRef<Path>) -> Result<Vec<String>, QrImageError> {
+    let image = image::open(path).map_err(|error| QrImageError::InvalidImage(error.to_string()))?;
+
+    barcode_uris_from_dynamic_image(&image)
+}
+
+/// Find the NZCP barcode URIs in an already decoded image. See [`barcode_uris_from_image`].
+pub fn barcode_uris_from_dynamic_image(image: &DynamicImage) -> Result<Vec<String>, QrImageError> {
+    let luma = image.to_luma8();
+    let mut other_codes = 0;
+
+    let attempts: [fn(&GrayImage) -> GrayImage; 3] = [Clone::clone, stretch_contrast, |luma| {
+        let mut inverted = luma.clone();
+        imageops::invert(&mut inverted);
+        inverted
+    }];
+
+    for attempt in attempts {
+        let contents = qr_contents(attempt(&luma));
+        let (uris, others): (Vec<_>, Vec<_>) = contents
+            .into_iter()
+            .partition(|content| content.starts_with(NZCP_PREFIX));
+
+        if !uris.is_empty() {
+            return Ok(uris);
+        }
+        other_codes = other_codes.max(others.len());
+    }
+
+    Err(QrImageError::NoNzcpQrCode(other_codes))
+}
+
+/// The contents of each QR code which could be decoded.
+fn qr_contents(luma: GrayImage) -> Vec<String> {
+    let mut prepared = rqrr::PreparedImage::prepare(luma);
+
+    prepared
+        .detect_grids()
+        .into_iter()
+        .filter_map(|grid| grid.decode().ok())
+        .map(|(_, content)| content)
+        .collect()
+}
+
+/// Scale the image's brightness to the full range, so faint codes have enough contrast to be thresholded.
+fn stretch_contrast(luma: &GrayImage) -> GrayImage {
+    let (min, max) = luma.pixels().fold((u8::MAX, u8::MIN), |(min, max), pixel| {
+        (min.min(pixel.0[0]), max.max(pixel.0[0]))
+    });
+    let range = u16::from(max.saturating_sub(min)).max(1);
+
+    let mut stretched = luma.clone();
+    for pixel in stretched.pixels_mut() {
+        pixel.0[0] = (u16::from(pixel.0[0] - min) * 255 / range) as u8;
+    }
+    stretched
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, Luma};
+    use qrcode::{EcLevel, QrCode};
+
+    use super::*;
+
+    // https://nzcp.covid19.health.nz/#valid-worked-example
+    const BARCODE: &str = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX";
+
+    fn render(content: &str) -> GrayImage {
+        QrCode::with_error_correction_level(content, EcLevel::M)
+            .unwrap()
+            .render::<Luma<u8>>()
+            .build()
+    }
+
+    fn png(image: GrayImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageLuma8(image)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn find_barcode() {
+        assert_eq!(barcode_uris_from_image(&png(render(BARCODE))).unwrap(), [BARCODE]);
+    }
+
+    #[test]
+    fn find_rotated_barcode() {
+        let rotated = imageops::rotate90(&render(BARCODE));
+
+        assert_eq!(barcode_uris_from_image(&png(rotated)).unwrap(), [BARCODE]);
+    }
+
+    #[test]
+    fn find_low_contrast_barcode() {
+        let mut faint = render(BARCODE);
+        for pixel in faint.pixels_mut() {
+            pixel.0[0] = if pixel.0[0] < 128 { 110 } else { 150 };
+        }
+
+        assert_eq!(barcode_uris_from_image(&png(faint)).unwrap(), [BARCODE]);
+    }
+
+    #[test]
+    fn find_inverted_barcode() {
+        let mut inverted = render(BARCODE);
+        imageops::invert(&mut inverted);
+
+        assert_eq!(barcode_uris_from_image(&png(inverted)).unwrap(), [BARCODE]);
+    }
+
+    #[test]
+    fn no_nzcp_barcode() {
+        assert_eq!(
+            barcode_uris_from_image(&png(render("https://example.com"))),
+            Err(QrImageError::NoNzcpQrCode(1))
+        );
+
+        let blank = GrayImage::from_pixel(100, 100, Luma([255]));
+        assert_eq!(barcode_uris_from_image(&png(blank)), Err(QrImageError::NoNzcpQrCode(0)));
+    }
+
+    #[test]
+    fn invalid_image() {
+        assert!(matches!(
+            barcode_uris_from_image(b"not an image"),
+            Err(QrImageError::InvalidImage(_))
+        ));
+    }
+}
@@ -0,0 +1,228 @@
+//! Rendering barcodes as QR codes, for printing passes and showing them in wallets. Enabled by the `render` feature.
+
+use std::{fmt::Write, io::Cursor};
+
+use image::{DynamicImage, GrayImage, ImageFormat, Luma};
+use qrcode::{bits::Bits, EcLevel, QrCode, Version};
+
+use super::{QrBarcode, QrRenderError};
+
+/// Renders barcodes as QR codes, as SVG or PNG.
+///
+/// QR codes use error correction level M, as recommended by the spec, and alphanumeric mode (which the `NZCP:/` prefix
+/// and base32 payload fit in) in the smallest QR version the barcode fits in.
+///
+/// ```ignore
+/// let barcode: QrBarcode = issuer.issue_pass_uri(&pass, cwt_token_id, not_before, expiry)?.parse()?;
+/// let png = QrCodeRenderer::new().module_size(4).png(&barcode)?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QrCodeRenderer {
+    quiet_zone: u32,
+    module_size: u32,
+}
+
+impl Default for QrCodeRenderer {
+    fn default() -> Self {
+        QrCodeRenderer {
+            quiet_zone: 4,
+            module_size: 8,
+        }
+    }
+}
+
+impl QrCodeRenderer {
+    /// A renderer with the standard 4 module quiet zone and 8 pixel modules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The width of the blank margin around the QR code, in modules. Scanners need at least 4, unless the QR code
+    /// is shown on a blank background anyway.
+    pub fn quiet_zone(mut self, modules: u32) -> Self {
+        self.quiet_zone = modules;
+        self
+    }
+
+    /// The width of each module (QR code "pixel"), in pixels for PNGs and user units for SVGs.
+    pub fn module_size(mut self, pixels: u32) -> Self {
+        self.module_size = pixels.max(1);
+        self
+    }
+
+    /// Render the barcode as an SVG document, with a path for the dark modules on a white background.
+    pub fn svg(&self, barcode: &QrBarcode) -> Result<String, QrRenderError> {
+        let code = qr_code(barcode)?;
+        let width = code.width() as u32;
+        let size = self.image_size(width)?;
+
+        let mut path = String::new();
+        for y in 0..width {
+            for x in 0..width {
+                if code[(x as usize, y as usize)] == qrcode::Color::Dark {
+                    let (left, top) = self.module_position(x, y);
+                    write!(path, "M{left} {top}h{0}v{0}h-{0}z", self.module_size).expect("writing to a string");
+                }
+            }
+        }
+
+        Ok(format!(
+            concat!(
+                r#"<?xml version="1.0" standalone="yes"?>"#,
+                r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{size}" height="{size}" "#,
+                r#"viewBox="0 0 {size} {size}" shape-rendering="crispEdges">"#,
+                r##"<rect width="100%" height="100%" fill="#fff"/><path fill="#000" d="{path}"/></svg>"##
+            ),
+            size = size,
+            path = path,
+        ))
+    }
+
+    /// Render the barcode as a black and white PNG image.
+    pub fn png(&self, barcode: &QrBarcode) -> Result<Vec<u8>, QrRenderError> {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageLuma8(self.image(barcode)?)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .expect("PNG encoding an image to memory");
+
+        Ok(bytes)
+    }
+
+    /// Render the barcode as a black and white image, e.g. to draw it onto a larger pass image.
+    pub fn image(&self, barcode: &QrBarcode) -> Result<GrayImage, QrRenderError> {
+        let code = qr_code(barcode)?;
+        let width = code.width() as u32;
+        let size = self.image_size(width)?;
+
+        let mut image = GrayImage::from_pixel(size, size, Luma([255]));
+        for y in 0..width {
+            for x in 0..width {
+                if code[(x as usize, y as usize)] == qrcode::Color::Dark {
+                    let (left, top) = self.module_position(x, y);
+                    for pixel_y in top..top + self.module_size {
+                        for pixel_x in left..left + self.module_size {
+                            image.put_pixel(pixel_x, pixel_y, Luma([0]));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(image)
+    }
+
+    /// The width and height of the image, failing if it can't be represented.
+    fn image_size(&self, width: u32) -> Result<u32, QrRenderError> {
+        self.quiet_zone
+            .checked_mul(2)
+            .and_then(|quiet_zones| quiet_zones.checked_add(width))
+            .and_then(|modules| modules.checked_mul(self.module_size))
+            .ok_or(QrRenderError::TooLarge {
+                quiet_zone: self.quiet_zone,
+                module_size: self.module_size,
+            })
+    }
+
+    /// The top left of the module, which can't overflow once the image size has been checked.
+    fn module_position(&self, x: u32, y: u32) -> (u32, u32) {
+        (
+            (x + self.quiet_zone) * self.module_size,
+            (y + self.quiet_zone) * self.module_size,
+        )
+    }
+}
+
+/// Encode the barcode in alphanumeric mode with error correction level M, in the smallest version it fits in.
+fn qr_code(barcode: &QrBarcode) -> Result<QrCode, QrRenderError> {
+    let uri = barcode.to_string();
+
+    (1..=40)
+        .find_map(|version| {
+            let mut bits = Bits::new(Version::Normal(version));
+            bits.push_alphanumeric_data(uri.as_bytes()).ok()?;
+            bits.push_terminator(EcLevel::M).ok()?;
+            QrCode::with_bits(bits, EcLevel::M).ok()
+        })
+        .ok_or(QrRenderError::TooLong(uri.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // https://nzcp.covid19.health.nz/#valid-worked-example
+    const BARCODE: &str = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX";
+
+    fn barcode() -> QrBarcode {
+        BARCODE.parse().unwrap()
+    }
+
+    #[test]
+    fn smallest_alphanumeric_code() {
+        let code = qr_code(&barcode()).unwrap();
+
+        assert_eq!(code.error_correction_level(), EcLevel::M);
+        assert_eq!(code.version(), Version::Normal(15));
+        assert_eq!(code.width(), 77);
+    }
+
+    #[test]
+    fn render_svg() {
+        let svg = QrCodeRenderer::new()
+            .quiet_zone(2)
+            .module_size(3)
+            .svg(&barcode())
+            .unwrap();
+
+        assert!(svg.contains(r#"width="243" height="243""#));
+        // the top left module of the finder pattern
+        assert!(svg.contains(r#"d="M6 6h3v3h-3z"#));
+    }
+
+    #[test]
+    fn render_image() {
+        let image = QrCodeRenderer::new()
+            .quiet_zone(2)
+            .module_size(3)
+            .image(&barcode())
+            .unwrap();
+
+        assert_eq!(image.dimensions(), (243, 243));
+        assert_eq!(image.get_pixel(5, 5), &Luma([255]));
+        assert_eq!(image.get_pixel(6, 6), &Luma([0]));
+        assert_eq!(image.get_pixel(8, 8), &Luma([0]));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn round_trip() {
+        let png = QrCodeRenderer::new().png(&barcode()).unwrap();
+
+        assert_eq!(super::super::image::barcode_uris_from_image(&png).unwrap(), [BARCODE]);
+    }
+
+    #[test]
+    fn too_long() {
+        let barcode = QrBarcode::new(vec![0; 3000]);
+
+        assert_eq!(QrCodeRenderer::new().svg(&barcode), Err(QrRenderError::TooLong(4808)));
+    }
+
+    #[test]
+    fn too_large() {
+        assert_eq!(
+            QrCodeRenderer::new().quiet_zone(u32::MAX / 2).svg(&barcode()),
+            Err(QrRenderError::TooLarge {
+                quiet_zone: u32::MAX / 2,
+                module_size: 8,
+            })
+        );
+        assert_eq!(
+            QrCodeRenderer::new().module_size(u32::MAX).image(&barcode()),
+            Err(QrRenderError::TooLarge {
+                quiet_zone: 4,
+                module_size: u32::MAX,
+            })
+        );
+    }
+}
@@ -10,10 +10,19 @@ use self::{
     protected_headers::ProtectedHeaders,
     signature::{verify::CoseVerificationError, CoseSignStructure, CoseSignature},
 };
-use super::cwt::CwtClaims;
-use crate::{decentralised_identifier::DecentralizedIdentifier, pass::Pass};
+use super::{
+    cwt::CwtClaims,
+    error::{from_slice, Checked, PayloadError},
+};
+use crate::{
+    decentralised_identifier::{
+        resolver::{BlockingKeyResolver, KeyResolver},
+        DecentralizedIdentifier,
+    },
+    pass::Credential,
+};
 
-mod protected_headers;
+pub mod protected_headers;
 pub mod signature;
 
 #[derive(Debug)]
@@ -23,26 +32,77 @@ pub struct CoseStructure<'a, T> {
     signature: CoseSignature<'a>,
 }
 
-impl<'a, T: Pass> CoseStructure<'a, T> {
-    /// Get the CWT payload iff the signature is valid.
+impl<'a, T> CoseStructure<'a, T> {
+    /// The key ID (`kid`) of the key the pass claims to be signed with.
+    pub fn kid(&self) -> &'a str {
+        self.protected_headers.kid
+    }
+
+    /// The protected headers, which have not been verified.
+    pub fn protected_headers(&self) -> &ProtectedHeaders<'a> {
+        &self.protected_headers
+    }
+
+    /// The CWT claims, which have not been verified.
+    pub fn cwt_claims(&self) -> &CwtClaims<'a, T> {
+        &self.cwt_claims
+    }
+
+    /// The signature, along with the raw segments it signs.
+    pub fn signature(&self) -> &CoseSignature<'a> {
+        &self.signature
+    }
+
+    pub(crate) fn into_cwt_claims(self) -> CwtClaims<'a, T> {
+        self.cwt_claims
+    }
+}
+
+impl<'a, T: Credential> CoseStructure<'a, T> {
+    /// Get the CWT payload iff the signature is valid, resolving the issuer's verifying key with the resolver.
     pub async fn verified_claims(
         self,
         trusted_issuers: &[DecentralizedIdentifier<'_>],
+        resolver: &dyn KeyResolver,
     ) -> Result<CwtClaims<'a, T>, CoseVerificationError> {
-        // TODO: caching
-        let verifying_key = self
-            .cwt_claims
-            .verify_issuer(trusted_issuers)?
-            .resolve_verifying_key(self.protected_headers.kid)
+        let verifying_key = resolver
+            .resolve_verifying_key(
+                self.cwt_claims.verify_issuer(trusted_issuers)?,
+                self.protected_headers.kid,
+            )
             .await?;
 
-        self.verify_signature(&verifying_key)?;
+        self.verified_claims_with_key(&verifying_key)
+    }
+
+    /// Get the CWT payload iff the signature is valid, resolving the issuer's verifying key synchronously.
+    pub fn verified_claims_blocking(
+        self,
+        trusted_issuers: &[DecentralizedIdentifier<'_>],
+        resolver: &dyn BlockingKeyResolver,
+    ) -> Result<CwtClaims<'a, T>, CoseVerificationError> {
+        let verifying_key = resolver.resolve_verifying_key_blocking(
+            self.cwt_claims.verify_issuer(trusted_issuers)?,
+            self.protected_headers.kid,
+        )?;
 
-        Ok(self.cwt_claims)
+        self.verified_claims_with_key(&verifying_key)
     }
 }
 
-impl<'de: 'a, 'a, T> Deserialize<'de> for CoseStructure<'a, T>
+impl<'a, T> CoseStructure<'a, T>
+where
+    T: Deserialize<'a>,
+{
+    /// Deserialize the COSE structure from the CBOR bytes of a barcode, without verifying it.
+    ///
+    /// Unlike deserializing with `serde_cbor` directly, structural defects are returned as typed errors.
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Self, PayloadError> {
+        from_slice(bytes)
+    }
+}
+
+impl<'de: 'a, 'a, T> Deserialize<'de> for Checked<CoseStructure<'a, T>>
 where
     T: Deserialize<'de>,
 {
@@ -50,25 +110,43 @@ where
     where
         D: Deserializer<'de>,
     {
-        let tagged: Tagged<CoseStructureSections<'_, T>> = Deserialize::deserialize(deserializer)?;
-        let CoseStructureSections {
-            protected_headers_raw,
-            cwt_claims_raw,
-            protected_headers,
-            cwt_claims,
-            signature,
-        } = tagged.value;
-
-        Ok(CoseStructure {
-            protected_headers,
-            cwt_claims,
-            signature: CoseSignature {
-                bytes: signature,
-                sign_structure: CoseSignStructure::try_from(tagged.tag).map_err(D::Error::custom)?,
+        let tagged: Tagged<Checked<CoseStructureSections<'_, T>>> = Deserialize::deserialize(deserializer)?;
+
+        // the tag is the outermost part of the structure, so report it first
+        let sign_structure = CoseSignStructure::try_from(tagged.tag).map_err(PayloadError::from);
+
+        Ok(Checked(sign_structure.and_then(|sign_structure| {
+            let CoseStructureSections {
                 protected_headers_raw,
                 cwt_claims_raw,
-            },
-        })
+                protected_headers,
+                cwt_claims,
+                signature,
+            } = tagged.value.0?;
+
+            Ok(CoseStructure {
+                protected_headers,
+                cwt_claims,
+                signature: CoseSignature {
+                    bytes: signature,
+                    sign_structure,
+                    protected_headers_raw,
+                    cwt_claims_raw,
+                },
+            })
+        })))
+    }
+}
+
+impl<'de: 'a, 'a, T> Deserialize<'de> for CoseStructure<'a, T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Checked::deserialize(deserializer)?.0.map_err(D::Error::custom)
     }
 }
 
@@ -89,7 +167,7 @@ impl<'de, T> Visitor<'de> for CoseStructureVisitor<T>
 where
     T: Deserialize<'de>,
 {
-    type Value = CoseStructureSections<'de, T>;
+    type Value = Checked<CoseStructureSections<'de, T>>;
 
     fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         formatter.write_str("COSE structure")
@@ -107,34 +185,33 @@ where
     where
         A: de::SeqAccess<'de>,
     {
-        let bytes = |name, seq: &mut A| {
-            seq.next_element()?
-                .ok_or_else(|| A::Error::custom(format!("missing COSE segment: {}", name)))
-        };
+        let protected_headers_raw: Option<&[u8]> = seq.next_element()?;
+        // unprotected headers are empty in spec, just skip them
+        let unprotected_headers: Option<IgnoredAny> = seq.next_element()?;
+        let cwt_claims_raw: Option<&[u8]> = seq.next_element()?;
+        let signature: Option<&[u8]> = seq.next_element()?;
 
-        let protected_headers_raw = bytes("protected headers", &mut seq)?;
-        let protected_headers = serde_cbor::from_slice(protected_headers_raw).map_err(A::Error::custom)?;
+        let sections = || {
+            let protected_headers_raw =
+                protected_headers_raw.ok_or(PayloadError::MissingCoseSegment("protected headers"))?;
+            unprotected_headers.ok_or(PayloadError::MissingCoseSegment("unprotected headers"))?;
+            let cwt_claims_raw = cwt_claims_raw.ok_or(PayloadError::MissingCoseSegment("CWT claims"))?;
+            let signature = signature.ok_or(PayloadError::MissingCoseSegment("signature"))?;
 
-        // unprotected headers are empty in spec, just skip them
-        let _: IgnoredAny = seq
-            .next_element()?
-            .ok_or_else(|| A::Error::custom("malformed COSE data (missing unprotected headers)"))?;
-
-        let cwt_claims_raw = bytes("CWT claims", &mut seq)?;
-        let cwt_claims = serde_cbor::from_slice(cwt_claims_raw).map_err(A::Error::custom)?;
-        let signature = bytes("signature", &mut seq)?;
-
-        Ok(CoseStructureSections {
-            protected_headers,
-            protected_headers_raw,
-            cwt_claims,
-            cwt_claims_raw,
-            signature,
-        })
+            Ok(CoseStructureSections {
+                protected_headers: from_slice(protected_headers_raw)?,
+                protected_headers_raw,
+                cwt_claims: from_slice(cwt_claims_raw)?,
+                cwt_claims_raw,
+                signature,
+            })
+        };
+
+        Ok(Checked(sections()))
     }
 }
 
-impl<'de: 'a, 'a, T> Deserialize<'de> for CoseStructureSections<'a, T>
+impl<'de: 'a, 'a, T> Deserialize<'de> for Checked<CoseStructureSections<'a, T>>
 where
     T: Deserialize<'de>,
 {
@@ -149,7 +226,10 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::payload::cose::signature::SignatureAlgorithm;
+    use crate::payload::{
+        cose::signature::SignatureAlgorithm,
+        error::{Claim, ProtectedHeader},
+    };
 
     #[test]
     fn deserialize_cose() {
@@ -165,4 +245,67 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn structural_errors() {
+        use std::collections::BTreeMap;
+
+        use serde::Serialize;
+        use serde_cbor::{tags::Tagged, Value};
+
+        use crate::payload::cose::signature::CoseSignStructureError;
+
+        fn error(value: &impl Serialize) -> PayloadError {
+            let bytes = serde_cbor::to_vec(value).unwrap();
+            CoseStructure::<Value>::from_slice(&bytes).unwrap_err()
+        }
+        let protected_headers = |headers: &[(i128, Value)]| {
+            let headers: BTreeMap<_, _> = headers
+                .iter()
+                .map(|(key, value)| (Value::Integer(*key), value.clone()))
+                .collect();
+            Value::Bytes(serde_cbor::to_vec(&headers).unwrap())
+        };
+        let kid = || (4, Value::Bytes(b"key-1".to_vec()));
+        let cose = |protected_headers: Value| {
+            Tagged::new(
+                Some(18),
+                vec![
+                    protected_headers,
+                    Value::Map(BTreeMap::new()),
+                    Value::Bytes(vec![0xa0]),
+                    Value::Bytes(vec![0; 64]),
+                ],
+            )
+        };
+
+        assert_eq!(
+            error(&Tagged::new(Some(18), vec![protected_headers(&[kid()])])),
+            PayloadError::MissingCoseSegment("unprotected headers")
+        );
+        assert_eq!(
+            error(&cose(protected_headers(&[kid()])).value),
+            PayloadError::SignStructure(CoseSignStructureError::MissingTag)
+        );
+        assert_eq!(
+            error(&cose(protected_headers(&[kid()]))),
+            PayloadError::MissingProtectedHeader(ProtectedHeader::Algorithm)
+        );
+        assert_eq!(
+            error(&cose(protected_headers(&[kid(), (1, Value::Integer(-37))]))),
+            PayloadError::UnsupportedAlgorithm(-37)
+        );
+        assert_eq!(
+            error(&cose(protected_headers(&[
+                kid(),
+                (1, Value::Integer(-7)),
+                (3, Value::Integer(-7))
+            ]))),
+            PayloadError::UnknownProtectedHeader(String::from("3"))
+        );
+        assert_eq!(
+            error(&cose(protected_headers(&[kid(), (1, Value::Integer(-7))]))),
+            PayloadError::MissingClaim(Claim::CwtTokenId)
+        );
+    }
 }
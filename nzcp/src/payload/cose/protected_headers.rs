@@ -2,13 +2,15 @@ use std::fmt;
 
 use serde::{
     de::{self, Error, Visitor},
-    Deserialize, Deserializer,
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
 };
 
 use super::signature::SignatureAlgorithm;
+use crate::payload::error::{describe_key, skip_value, Checked, PayloadError, ProtectedHeader};
 
-const KID_KEY: u8 = 4;
-const ALG_KEY: u8 = 1;
+pub(crate) const KID_KEY: i128 = 4;
+pub(crate) const ALG_KEY: i128 = 1;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct ProtectedHeaders<'a> {
@@ -19,7 +21,7 @@ pub struct ProtectedHeaders<'a> {
 struct ProtectedHeadersVisitor;
 
 impl<'de> Visitor<'de> for ProtectedHeadersVisitor {
-    type Value = ProtectedHeaders<'de>;
+    type Value = Checked<ProtectedHeaders<'de>>;
 
     fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         formatter.write_str("COSE protected headers")
@@ -31,24 +33,41 @@ impl<'de> Visitor<'de> for ProtectedHeadersVisitor {
     {
         let mut kid = None;
         let mut algorithm = None;
+        let mut error = None;
 
         while let Some(key) = map.next_key()? {
             match key {
-                KID_KEY => kid = Some(map.next_value()?),
-                ALG_KEY => algorithm = Some(map.next_value::<i8>()?.try_into().map_err(A::Error::custom)?),
-                _ => return Err(A::Error::unknown_field(&format!("{}", key), &["4 (kid)", "1 (alg)"])),
+                serde_cbor::Value::Integer(KID_KEY) => kid = Some(map.next_value()?),
+                serde_cbor::Value::Integer(ALG_KEY) => {
+                    let alg: i64 = map.next_value()?;
+                    match i8::try_from(alg)
+                        .ok()
+                        .and_then(|alg| SignatureAlgorithm::try_from(alg).ok())
+                    {
+                        Some(alg) => algorithm = Some(alg),
+                        None => {
+                            error.get_or_insert(PayloadError::UnsupportedAlgorithm(alg));
+                        }
+                    }
+                }
+                key => skip_value(
+                    &mut map,
+                    &mut error,
+                    PayloadError::UnknownProtectedHeader(describe_key(&key)),
+                )?,
             }
         }
 
-        match (kid, algorithm) {
-            (Some(kid), Some(algorithm)) => Ok(ProtectedHeaders { kid, algorithm }),
-            (_, None) => Err(A::Error::missing_field("1 (alg)")),
-            (None, _) => Err(A::Error::missing_field("4 (kid)")),
-        }
+        Ok(Checked(match (error, kid, algorithm) {
+            (Some(error), ..) => Err(error),
+            (None, Some(kid), Some(algorithm)) => Ok(ProtectedHeaders { kid, algorithm }),
+            (None, _, None) => Err(PayloadError::MissingProtectedHeader(ProtectedHeader::Algorithm)),
+            (None, None, _) => Err(PayloadError::MissingProtectedHeader(ProtectedHeader::KeyId)),
+        }))
     }
 }
 
-impl<'de: 'a, 'a> Deserialize<'de> for ProtectedHeaders<'a> {
+impl<'de: 'a, 'a> Deserialize<'de> for Checked<ProtectedHeaders<'a>> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
@@ -56,3 +75,25 @@ impl<'de: 'a, 'a> Deserialize<'de> for ProtectedHeaders<'a> {
         deserializer.deserialize_map(ProtectedHeadersVisitor)
     }
 }
+
+impl<'de: 'a, 'a> Deserialize<'de> for ProtectedHeaders<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Checked::deserialize(deserializer)?.0.map_err(D::Error::custom)
+    }
+}
+
+impl<'a> Serialize for ProtectedHeaders<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // the spec encodes the kid as a byte string
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry(&KID_KEY, serde_bytes::Bytes::new(self.kid.as_bytes()))?;
+        map.serialize_entry(&ALG_KEY, &i8::from(&self.algorithm))?;
+        map.end()
+    }
+}
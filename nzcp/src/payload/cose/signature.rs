@@ -1,6 +1,8 @@
-use thiserror::Error;
+use std::fmt;
 
+use thiserror::Error;
 
+pub mod sign;
 pub mod verify;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -18,7 +20,8 @@ pub enum CoseSignStructure {
     Sign1,
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum CoseSignStructureError {
     #[error("missing CBOR tag (infering the sign structure)")]
     MissingTag,
@@ -39,15 +42,31 @@ impl TryFrom<Option<u64>> for CoseSignStructure {
 }
 
 const ES256_ID: i8 = -7;
+const ES384_ID: i8 = -35;
+const ES512_ID: i8 = -36;
+const EDDSA_ID: i8 = -8;
 
-#[derive(Debug, PartialEq, Eq)]
+/// The signature algorithms NZCP permits, which are allowed by default.
+pub const NZCP_ALGORITHMS: [SignatureAlgorithm; 1] = [SignatureAlgorithm::Es256];
+
+/// A COSE signature algorithm, of those which can be verified. Only ES256 is permitted by NZCP; the others are for
+/// verifying similar passes from other issuers, and must be allowed explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SignatureAlgorithm {
+    /// ECDSA with P-256 and SHA-256.
     Es256,
+    /// ECDSA with P-384 and SHA-384.
+    Es384,
+    /// ECDSA with P-521 and SHA-512.
+    Es512,
+    /// EdDSA with Ed25519.
+    EdDsa,
 }
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum SignatureAlgorithmError {
-    #[error("invalid CBOR signature algorithm (must be ES256)")]
+    #[error("invalid CBOR signature algorithm (must be ES256, ES384, ES512 or EdDSA)")]
     SignatureAlgorithm,
 }
 
@@ -57,7 +76,32 @@ impl TryFrom<i8> for SignatureAlgorithm {
     fn try_from(value: i8) -> Result<Self, Self::Error> {
         match value {
             ES256_ID => Ok(SignatureAlgorithm::Es256),
+            ES384_ID => Ok(SignatureAlgorithm::Es384),
+            ES512_ID => Ok(SignatureAlgorithm::Es512),
+            EDDSA_ID => Ok(SignatureAlgorithm::EdDsa),
             _ => Err(SignatureAlgorithmError::SignatureAlgorithm),
         }
     }
 }
+
+impl fmt::Display for SignatureAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureAlgorithm::Es256 => write!(f, "ES256"),
+            SignatureAlgorithm::Es384 => write!(f, "ES384"),
+            SignatureAlgorithm::Es512 => write!(f, "ES512"),
+            SignatureAlgorithm::EdDsa => write!(f, "EdDSA"),
+        }
+    }
+}
+
+impl From<&SignatureAlgorithm> for i8 {
+    fn from(algorithm: &SignatureAlgorithm) -> Self {
+        match algorithm {
+            SignatureAlgorithm::Es256 => ES256_ID,
+            SignatureAlgorithm::Es384 => ES384_ID,
+            SignatureAlgorithm::Es512 => ES512_ID,
+            SignatureAlgorithm::EdDsa => EDDSA_ID,
+        }
+    }
+}
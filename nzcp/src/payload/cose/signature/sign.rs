@@ -0,0 +1,53 @@
+use std::collections::BTreeMap;
+
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use serde::Serialize;
+use serde_cbor::tags::Tagged;
+
+use super::{CoseSignStructure, CoseSignature, SIGN1_TAG};
+use crate::payload::{cose::protected_headers::ProtectedHeaders, cwt::CwtClaims};
+
+/// Sign the protected headers and CWT claims with an ES256 key, returning the encoded `COSE_Sign1` structure.
+pub fn sign1<T: Serialize>(
+    protected_headers: &ProtectedHeaders<'_>,
+    cwt_claims: &CwtClaims<'_, T>,
+    signing_key: &SigningKey,
+) -> Result<Vec<u8>, serde_cbor::Error> {
+    sign1_with(protected_headers, cwt_claims, |to_be_signed| {
+        let signature: Signature = signing_key.sign(to_be_signed);
+        signature.to_vec()
+    })
+}
+
+/// Sign the protected headers and CWT claims with any algorithm, returning the encoded `COSE_Sign1` structure. `sign`
+/// signs the bytes with the protected headers' algorithm, returning the signature in its COSE encoding.
+pub fn sign1_with<T: Serialize>(
+    protected_headers: &ProtectedHeaders<'_>,
+    cwt_claims: &CwtClaims<'_, T>,
+    sign: impl FnOnce(&[u8]) -> Vec<u8>,
+) -> Result<Vec<u8>, serde_cbor::Error> {
+    let protected_headers_raw = serde_cbor::to_vec(protected_headers)?;
+    let cwt_claims_raw = serde_cbor::to_vec(cwt_claims)?;
+
+    let unsigned = CoseSignature {
+        bytes: &[],
+        sign_structure: CoseSignStructure::Sign1,
+        protected_headers_raw: &protected_headers_raw,
+        cwt_claims_raw: &cwt_claims_raw,
+    };
+    let to_be_signed = serde_cbor::to_vec(&unsigned.sig_structure())?;
+    let signature = sign(&to_be_signed);
+
+    // unprotected headers are empty in spec
+    let unprotected_headers: BTreeMap<i8, ()> = BTreeMap::new();
+
+    serde_cbor::to_vec(&Tagged::new(
+        Some(SIGN1_TAG),
+        (
+            serde_bytes::Bytes::new(&protected_headers_raw),
+            unprotected_headers,
+            serde_bytes::Bytes::new(&cwt_claims_raw),
+            serde_bytes::Bytes::new(&signature),
+        ),
+    ))
+}
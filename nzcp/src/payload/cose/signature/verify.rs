@@ -1,19 +1,21 @@
-use p256::ecdsa::{
-    self,
-    signature::{Signature, Verifier},
-    VerifyingKey,
-};
+use std::fmt;
+
+use p256::ecdsa::signature::Verifier as _;
 use serde::Serialize;
 use thiserror::Error;
 
-use super::{CoseSignStructure, CoseSignature};
+use super::{CoseSignStructure, CoseSignature, SignatureAlgorithm};
 use crate::{
     decentralised_identifier::DecentralizedIdentifierError,
-    payload::{cose::CoseStructure, cwt::validation::CwtValidationError},
+    payload::{
+        cose::CoseStructure,
+        cwt::{validation::CwtValidationError, CwtClaims},
+    },
 };
 
 /// A deliberately opaque signature error
 #[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum CoseVerificationError {
     #[error("signature verification failed")]
     VerificationFailed,
@@ -23,28 +25,167 @@ pub enum CoseVerificationError {
     UntrustedIssuer(String),
     #[error("DID resolution failed: {0:?}")]
     DecentralizedIdentifierResolution(#[from] DecentralizedIdentifierError),
+    #[error("signature algorithm {0} is not allowed")]
+    DisallowedAlgorithm(SignatureAlgorithm),
+    #[error("the issuer's key is for {key_algorithm}, not {algorithm}")]
+    KeyAlgorithmMismatch {
+        algorithm: SignatureAlgorithm,
+        key_algorithm: SignatureAlgorithm,
+    },
 }
 
-impl<'a, T> CoseStructure<'a, T> {
-    pub fn verify_signature(&self, verifying_key: &VerifyingKey) -> Result<(), CoseVerificationError> {
+/// An issuer's public key, which verifies signatures made with one signature algorithm.
+#[derive(Clone)]
+pub enum VerifyingKey {
+    /// A P-256 key, for ES256.
+    Es256(p256::ecdsa::VerifyingKey),
+    /// A P-384 key, for ES384.
+    Es384(p384::ecdsa::VerifyingKey),
+    /// A P-521 key, for ES512.
+    Es512(p521::ecdsa::VerifyingKey),
+    /// An Ed25519 key, for EdDSA.
+    EdDsa(ed25519_dalek::VerifyingKey),
+}
+
+impl VerifyingKey {
+    /// The signature algorithm the key verifies.
+    pub fn algorithm(&self) -> SignatureAlgorithm {
+        match self {
+            VerifyingKey::Es256(_) => SignatureAlgorithm::Es256,
+            VerifyingKey::Es384(_) => SignatureAlgorithm::Es384,
+            VerifyingKey::Es512(_) => SignatureAlgorithm::Es512,
+            VerifyingKey::EdDsa(_) => SignatureAlgorithm::EdDsa,
+        }
+    }
+
+    /// The public key, as an uncompressed SEC1 point for ECDSA keys or the 32 byte key for Ed25519 keys.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            VerifyingKey::Es256(key) => key.to_encoded_point(false).as_bytes().to_vec(),
+            VerifyingKey::Es384(key) => key.to_encoded_point(false).as_bytes().to_vec(),
+            VerifyingKey::Es512(key) => key.to_encoded_point(false).as_bytes().to_vec(),
+            VerifyingKey::EdDsa(key) => key.to_bytes().to_vec(),
+        }
+    }
+
+    /// Verify a signature in its COSE encoding, which for ECDSA is `r` and `s` concatenated.
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), CoseVerificationError> {
         use CoseVerificationError::VerificationFailed;
 
-        let sig_structure = self.signature.sig_structure();
-        let to_be_signed = serde_cbor::to_vec(&sig_structure).map_err(|_| VerificationFailed)?;
+        let verified = match self {
+            VerifyingKey::Es256(key) => p256::ecdsa::Signature::from_slice(signature)
+                .and_then(|signature| key.verify(message, &signature))
+                .is_ok(),
+            VerifyingKey::Es384(key) => p384::ecdsa::Signature::from_slice(signature)
+                .and_then(|signature| key.verify(message, &signature))
+                .is_ok(),
+            VerifyingKey::Es512(key) => p521::ecdsa::Signature::from_slice(signature)
+                .and_then(|signature| key.verify(message, &signature))
+                .is_ok(),
+            VerifyingKey::EdDsa(key) => ed25519_dalek::Signature::from_slice(signature)
+                .and_then(|signature| key.verify_strict(message, &signature))
+                .is_ok(),
+        };
+
+        verified.then_some(()).ok_or(VerificationFailed)
+    }
+}
+
+impl From<p256::ecdsa::VerifyingKey> for VerifyingKey {
+    fn from(key: p256::ecdsa::VerifyingKey) -> Self {
+        VerifyingKey::Es256(key)
+    }
+}
+
+impl From<p384::ecdsa::VerifyingKey> for VerifyingKey {
+    fn from(key: p384::ecdsa::VerifyingKey) -> Self {
+        VerifyingKey::Es384(key)
+    }
+}
+
+impl From<p521::ecdsa::VerifyingKey> for VerifyingKey {
+    fn from(key: p521::ecdsa::VerifyingKey) -> Self {
+        VerifyingKey::Es512(key)
+    }
+}
+
+impl From<ed25519_dalek::VerifyingKey> for VerifyingKey {
+    fn from(key: ed25519_dalek::VerifyingKey) -> Self {
+        VerifyingKey::EdDsa(key)
+    }
+}
+
+// not derived, as P-521 keys implement neither
+impl PartialEq for VerifyingKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.algorithm() == other.algorithm() && self.to_bytes() == other.to_bytes()
+    }
+}
+
+impl Eq for VerifyingKey {}
+
+impl fmt::Debug for VerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key: String = self.to_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+        f.debug_struct("VerifyingKey")
+            .field("algorithm", &self.algorithm())
+            .field("key", &key)
+            .finish()
+    }
+}
+
+impl<'a> CoseSignature<'a> {
+    /// Verify the signature was made with the algorithm, failing if the key is for a different algorithm.
+    pub fn verify(
+        &self,
+        algorithm: SignatureAlgorithm,
+        verifying_key: &VerifyingKey,
+    ) -> Result<(), CoseVerificationError> {
+        let key_algorithm = verifying_key.algorithm();
+        if key_algorithm != algorithm {
+            return Err(CoseVerificationError::KeyAlgorithmMismatch {
+                algorithm,
+                key_algorithm,
+            });
+        }
+
+        let sig_structure = self.sig_structure();
+        let to_be_signed = serde_cbor::to_vec(&sig_structure).map_err(|_| CoseVerificationError::VerificationFailed)?;
+
+        verifying_key.verify(&to_be_signed, self.bytes)
+    }
+}
+
+impl<'a, T> CoseStructure<'a, T> {
+    /// Fail unless the signature algorithm is one of those allowed, e.g. [`NZCP_ALGORITHMS`](super::NZCP_ALGORITHMS).
+    pub fn verify_algorithm(&self, allowed_algorithms: &[SignatureAlgorithm]) -> Result<(), CoseVerificationError> {
+        let algorithm = self.protected_headers().algorithm;
+        if allowed_algorithms.contains(&algorithm) {
+            Ok(())
+        }
+        else {
+            Err(CoseVerificationError::DisallowedAlgorithm(algorithm))
+        }
+    }
+
+    pub fn verify_signature(&self, verifying_key: &VerifyingKey) -> Result<(), CoseVerificationError> {
+        self.signature()
+            .verify(self.protected_headers().algorithm, verifying_key)
+    }
 
-        verifying_key
-            .verify(
-                &to_be_signed,
-                &ecdsa::Signature::from_bytes(self.signature.bytes).map_err(|_| VerificationFailed)?,
-            )
-            .map_err(|_| VerificationFailed)?;
+    /// Get the CWT payload iff the signature is valid for the given key.
+    pub fn verified_claims_with_key(
+        self,
+        verifying_key: &VerifyingKey,
+    ) -> Result<CwtClaims<'a, T>, CoseVerificationError> {
+        self.verify_signature(verifying_key)?;
 
-        Ok(())
+        Ok(self.cwt_claims)
     }
 }
 
 #[derive(Serialize, Debug)]
-struct SignatureStructure<'a>(
+pub(super) struct SignatureStructure<'a>(
     &'static str,
     // TODO: comment what each of these are
     #[serde(with = "serde_bytes")] &'a [u8],
@@ -53,7 +194,7 @@ struct SignatureStructure<'a>(
 );
 
 impl<'a> CoseSignature<'a> {
-    fn sig_structure(&self) -> SignatureStructure<'a> {
+    pub(super) fn sig_structure(&self) -> SignatureStructure<'a> {
         match self.sign_structure {
             CoseSignStructure::Sign1 => {
                 SignatureStructure("Signature1", self.protected_headers_raw, &[], self.cwt_claims_raw)
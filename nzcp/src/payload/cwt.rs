@@ -3,40 +3,115 @@ use std::{fmt, marker::PhantomData};
 use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{
     de::{self, Error, Visitor},
-    Deserialize, Deserializer,
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
 };
 use uuid::Uuid;
 
-use self::validation::CwtValidationError;
-use crate::{decentralised_identifier::DecentralizedIdentifier, pass::Pass};
+use self::validation::{CwtValidationError, Leeway, CREDENTIAL_VERSION, MANDATAORY_CONTEXT_URL, MANDATAORY_TYPE};
+use crate::{
+    decentralised_identifier::DecentralizedIdentifier,
+    pass::{verified_pass::VerifiedPass, Credential, Pass},
+    payload::error::{describe_key, skip_value, Checked, Claim, PayloadError},
+};
 
 pub mod validation;
 
 const CWT_TOKEN_CLAIM_KEY: i128 = 7;
-const ISSUER_CLAIM_KEY: i128 = 1;
-const NOT_BEFORE_CLAIM_KEY: i128 = 5;
-const EXPIRY_CLAIM_KEY: i128 = 4;
-const VERIFIABLE_CREDENTIAL_KEY: &'static str = "vc";
-const EXPECTED_KEYS: [&'static str; 5] = ["7 (cwt)", "1 (iss)", "5 (nbf)", "4 (exp)", "vc"];
+pub(crate) const ISSUER_CLAIM_KEY: i128 = 1;
+pub(crate) const NOT_BEFORE_CLAIM_KEY: i128 = 5;
+pub(crate) const EXPIRY_CLAIM_KEY: i128 = 4;
+pub(crate) const VERIFIABLE_CREDENTIAL_KEY: &str = "vc";
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct CwtClaims<'a, T> {
-    cwt_token_id: Uuid,
-    issuer: DecentralizedIdentifier<'a>,
-    not_before: DateTime<Utc>,
-    expiry: DateTime<Utc>,
-    verifiable_credential: VerifiableCredential<'a, T>,
+    pub(crate) cwt_token_id: Uuid,
+    pub(crate) issuer: DecentralizedIdentifier<'a>,
+    pub(crate) not_before: DateTime<Utc>,
+    pub(crate) expiry: DateTime<Utc>,
+    pub(crate) verifiable_credential: VerifiableCredential<'a, T>,
 }
 
-impl<'a, T: Pass> CwtClaims<'a, T> {
-    pub fn validated_credential_subject(self) -> Result<T, CwtValidationError> {
-        self.validate()?;
-        Ok(self.verifiable_credential.credential_subject)
+impl<'a, P: Pass> CwtClaims<'a, &'a P> {
+    /// Create the claims for issuing the given pass.
+    pub fn new(
+        cwt_token_id: Uuid,
+        issuer: DecentralizedIdentifier<'a>,
+        not_before: DateTime<Utc>,
+        expiry: DateTime<Utc>,
+        pass: &'a P,
+    ) -> Self {
+        CwtClaims {
+            cwt_token_id,
+            issuer,
+            not_before,
+            expiry,
+            verifiable_credential: VerifiableCredential {
+                context: vec![MANDATAORY_CONTEXT_URL, P::CONTEXT_URL],
+                version: CREDENTIAL_VERSION,
+                _type: (MANDATAORY_TYPE, P::CREDENTIAL_TYPE),
+                credential_subject: pass,
+            },
+        }
     }
 }
 
-fn utc_from_timestamp(epoch_seconds: i64) -> DateTime<Utc> {
-    DateTime::from_utc(NaiveDateTime::from_timestamp(epoch_seconds, 0), Utc)
+impl<'a, T> CwtClaims<'a, T> {
+    /// The CWT token ID (`jti` claim).
+    pub fn cwt_token_id(&self) -> Uuid {
+        self.cwt_token_id
+    }
+
+    /// The issuer (`iss` claim).
+    pub fn issuer(&self) -> DecentralizedIdentifier<'a> {
+        self.issuer
+    }
+
+    /// The not before date (`nbf` claim).
+    pub fn not_before(&self) -> DateTime<Utc> {
+        self.not_before
+    }
+
+    /// The expiry date (`exp` claim).
+    pub fn expiry(&self) -> DateTime<Utc> {
+        self.expiry
+    }
+
+    /// The verifiable credential (`vc` claim).
+    pub fn verifiable_credential(&self) -> &VerifiableCredential<'a, T> {
+        &self.verifiable_credential
+    }
+}
+
+impl<'a, T: Credential> CwtClaims<'a, T> {
+    /// Get the pass iff the claims are valid as at `now`, along with the claims and any leeway needed to accept it.
+    pub fn validated_pass(
+        self,
+        kid: &str,
+        now: DateTime<Utc>,
+        leeway: Leeway,
+    ) -> Result<VerifiedPass<T>, CwtValidationError> {
+        let applied_leeway = self.validate(now, leeway)?;
+        let version = self.verifiable_credential.version;
+        let pass = self.verifiable_credential.into_pass()?;
+
+        Ok(VerifiedPass {
+            cwt_token_id: self.cwt_token_id,
+            issuer: self.issuer.did(),
+            kid: kid.to_owned(),
+            not_before: self.not_before,
+            expiry: self.expiry,
+            version: version.to_owned(),
+            applied_leeway,
+            normalisations: Vec::new(),
+            pass,
+        })
+    }
+}
+
+/// The date for a CWT timestamp, or `None` if it's out of range.
+pub(crate) fn utc_from_timestamp(epoch_seconds: i64) -> Option<DateTime<Utc>> {
+    NaiveDateTime::from_timestamp_opt(epoch_seconds, 0).map(|date| DateTime::from_utc(date, Utc))
 }
 
 /// CWT claims contain integer keys, so we need to manually deserialize.
@@ -46,10 +121,10 @@ impl<'de, T> Visitor<'de> for CwtClaimsVisitor<T>
 where
     T: Deserialize<'de>,
 {
-    type Value = CwtClaims<'de, T>;
+    type Value = Checked<CwtClaims<'de, T>>;
 
     fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        formatter.write_str("COSE protected headers")
+        formatter.write_str("CWT claims")
     }
 
     fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
@@ -61,46 +136,69 @@ where
         let mut not_before = None;
         let mut expiry = None;
         let mut verifiable_credential = None;
+        let mut error = None;
+
+        let date = |seconds: i64, claim, error: &mut Option<PayloadError>| {
+            let date = utc_from_timestamp(seconds);
+            if date.is_none() {
+                error.get_or_insert(PayloadError::InvalidDate(claim));
+            }
+            date
+        };
 
         while let Some(key) = map.next_key()? {
             use serde_cbor::Value::{Integer, Text};
             match key {
-                Integer(CWT_TOKEN_CLAIM_KEY) => cwt_token_id = Some(map.next_value()?),
-                Integer(ISSUER_CLAIM_KEY) => issuer = Some(map.next_value()?),
-                Integer(NOT_BEFORE_CLAIM_KEY) => not_before = Some(utc_from_timestamp(map.next_value()?)),
-                Integer(EXPIRY_CLAIM_KEY) => expiry = Some(utc_from_timestamp(map.next_value()?)),
-                Text(text_key) => {
-                    if text_key == VERIFIABLE_CREDENTIAL_KEY {
-                        verifiable_credential = Some(map.next_value()?);
+                Integer(CWT_TOKEN_CLAIM_KEY) => {
+                    cwt_token_id = Uuid::from_slice(map.next_value()?).ok();
+                    if cwt_token_id.is_none() {
+                        error.get_or_insert(PayloadError::InvalidCwtTokenId);
                     }
-                    else {
-                        return Err(A::Error::unknown_field(&text_key, &EXPECTED_KEYS));
+                }
+                Integer(ISSUER_CLAIM_KEY) => {
+                    let did = map.next_value()?;
+                    issuer = DecentralizedIdentifier::parse(did);
+                    if issuer.is_none() {
+                        error.get_or_insert(PayloadError::InvalidIssuer(did.to_owned()));
                     }
                 }
-                _ => return Err(A::Error::unknown_field(&format!("{:?}", key), &EXPECTED_KEYS)),
+                Integer(NOT_BEFORE_CLAIM_KEY) => not_before = date(map.next_value()?, Claim::NotBefore, &mut error),
+                Integer(EXPIRY_CLAIM_KEY) => expiry = date(map.next_value()?, Claim::Expiry, &mut error),
+                Text(text_key) if text_key == VERIFIABLE_CREDENTIAL_KEY => {
+                    verifiable_credential = Some(map.next_value()?);
+                }
+                key => skip_value(&mut map, &mut error, PayloadError::UnknownClaim(describe_key(&key)))?,
             }
         }
 
-        match (cwt_token_id, issuer, not_before, expiry, verifiable_credential) {
-            (Some(cwt_token_id), Some(issuer), Some(not_before), Some(expiry), Some(verifiable_credential)) => {
-                Ok(CwtClaims {
+        Ok(Checked(
+            match (error, cwt_token_id, issuer, not_before, expiry, verifiable_credential) {
+                (Some(error), ..) => Err(error),
+                (
+                    None,
+                    Some(cwt_token_id),
+                    Some(issuer),
+                    Some(not_before),
+                    Some(expiry),
+                    Some(verifiable_credential),
+                ) => Ok(CwtClaims {
                     cwt_token_id,
                     issuer,
                     not_before,
                     expiry,
                     verifiable_credential,
-                })
-            }
-            (None, ..) => Err(A::Error::missing_field("7 (cwt)")),
-            (_, None, ..) => Err(A::Error::missing_field("1 (iss)")),
-            (_, _, None, ..) => Err(A::Error::missing_field("5 (nbf)")),
-            (_, _, _, None, ..) => Err(A::Error::missing_field("4 (exp)")),
-            (.., None) => Err(A::Error::missing_field("vc")),
-        }
+                }),
+                (None, None, ..) => Err(PayloadError::MissingClaim(Claim::CwtTokenId)),
+                (None, _, None, ..) => Err(PayloadError::MissingClaim(Claim::Issuer)),
+                (None, _, _, None, ..) => Err(PayloadError::MissingClaim(Claim::NotBefore)),
+                (None, _, _, _, None, ..) => Err(PayloadError::MissingClaim(Claim::Expiry)),
+                (None, .., None) => Err(PayloadError::MissingClaim(Claim::VerifiableCredential)),
+            },
+        ))
     }
 }
 
-impl<'de: 'a, 'a, T> Deserialize<'de> for CwtClaims<'a, T>
+impl<'de: 'a, 'a, T> Deserialize<'de> for Checked<CwtClaims<'a, T>>
 where
     T: Deserialize<'de>,
 {
@@ -112,8 +210,39 @@ where
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
-struct VerifiableCredential<'a, T> {
+impl<'de: 'a, 'a, T> Deserialize<'de> for CwtClaims<'a, T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Checked::deserialize(deserializer)?.0.map_err(D::Error::custom)
+    }
+}
+
+/// Serialized in the same key order as the spec examples.
+impl<'a, T> Serialize for CwtClaims<'a, T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(5))?;
+        map.serialize_entry(&ISSUER_CLAIM_KEY, &self.issuer)?;
+        map.serialize_entry(&NOT_BEFORE_CLAIM_KEY, &self.not_before.timestamp())?;
+        map.serialize_entry(&EXPIRY_CLAIM_KEY, &self.expiry.timestamp())?;
+        map.serialize_entry(VERIFIABLE_CREDENTIAL_KEY, &self.verifiable_credential)?;
+        map.serialize_entry(&CWT_TOKEN_CLAIM_KEY, &self.cwt_token_id)?;
+        map.end()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct VerifiableCredential<'a, T> {
     /// JSON-LD Context property for conformance to the W3C VC standard. This property MUST be present and its value MUST be an array of strings where the first value MUST equal https://www.w3.org/2018/credentials/v1.
     ///
     /// The following is an example including an additional JSON-LD context entry that defines the additional vocabulary specific to the New Zealand COVID Pass.
@@ -121,7 +250,10 @@ struct VerifiableCredential<'a, T> {
     /// ["https://www.w3.org/2018/credentials/v1", "https://nzcp.covid19.health.nz/contexts/v1"]
     /// ```
     #[serde(rename = "@context")]
-    context: Vec<&'a str>,
+    pub(crate) context: Vec<&'a str>,
+
+    /// Version property of the New Zealand Covid Pass. This property MUST be present and its value MUST be a string who’s value corresponds to a valid version identifier as defined by semver. For the purposes of this version of the specification this value MUST be 1.0.0.
+    pub(crate) version: &'a str,
 
     /// Type property for conformance to the W3C VC standard. This property MUST be present and its value MUST be an array of two string values, whose first element is VerifiableCredential and second element corresponds to one defined in the pass types section.
     ///
@@ -130,19 +262,41 @@ struct VerifiableCredential<'a, T> {
     /// ["VerifiableCredential", "PublicCovidPass"]
     /// ```
     #[serde(rename = "type")]
-    _type: (&'a str, &'a str),
-
-    /// Version property of the New Zealand Covid Pass. This property MUST be present and its value MUST be a string who’s value corresponds to a valid version identifier as defined by semver. For the purposes of this version of the specification this value MUST be 1.0.0.
-    version: &'a str,
+    pub(crate) _type: (&'a str, &'a str),
 
     /// Credential Subject property MUST be present and its value MUST be a JSON object with properties determined by the declared pass type for the pass.
     #[serde(rename = "credentialSubject")]
-    credential_subject: T,
+    pub(crate) credential_subject: T,
+}
+
+impl<'a, T> VerifiableCredential<'a, T> {
+    /// The JSON-LD contexts (`@context`).
+    pub fn context(&self) -> &[&'a str] {
+        &self.context
+    }
+
+    /// The credential version (e.g. `1.0.0`).
+    pub fn version(&self) -> &'a str {
+        self.version
+    }
+
+    /// The credential type (`type`), e.g. `["VerifiableCredential", "PublicCovidPass"]`.
+    pub fn credential_type(&self) -> (&'a str, &'a str) {
+        self._type
+    }
+
+    /// The credential subject (`credentialSubject`), i.e. the pass.
+    pub fn credential_subject(&self) -> &T {
+        &self.credential_subject
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use super::{
+        validation::{AppliedLeeway, LeewayError},
+        *,
+    };
 
     #[test]
     fn deserialize_cbor() {
@@ -157,18 +311,188 @@ mod tests {
             CwtClaims {
                 cwt_token_id: Uuid::parse_str("urn:uuid:60a4f54d-4e30-4332-be33-ad78b1eafa4b").unwrap(),
                 issuer: DecentralizedIdentifier::Web("nzcp.covid19.health.nz"),
-                not_before: utc_from_timestamp(1635883530),
-                expiry: utc_from_timestamp(1951416330),
+                not_before: utc_from_timestamp(1635883530).unwrap(),
+                expiry: utc_from_timestamp(1951416330).unwrap(),
                 verifiable_credential: VerifiableCredential {
                     context: vec![
                         "https://www.w3.org/2018/credentials/v1",
                         "https://nzcp.covid19.health.nz/contexts/v1"
                     ],
-                    _type: ("VerifiableCredential", "PublicCovidPass"),
                     version: "1.0.0",
+                    _type: ("VerifiableCredential", "PublicCovidPass"),
                     credential_subject: "helloworld",
                 }
             }
         )
     }
+
+    #[test]
+    fn validate_at() {
+        use crate::PublicCovidPass;
+
+        let bytes = hex::decode("a501781e6469643a7765623a6e7a63702e636f76696431392e6865616c74682e6e7a051a61819a0a041a7450400a627663a46840636f6e7465787482782668747470733a2f2f7777772e77332e6f72672f323031382f63726564656e7469616c732f7631782a68747470733a2f2f6e7a63702e636f76696431392e6865616c74682e6e7a2f636f6e74657874732f76316776657273696f6e65312e302e306474797065827456657269666961626c6543726564656e7469616c6f5075626c6963436f766964506173737163726564656e7469616c5375626a656374a369676976656e4e616d65644a61636b6a66616d696c794e616d656753706172726f7763646f626a313936302d30342d3136075060a4f54d4e304332be33ad78b1eafa4b").unwrap();
+        let claims: CwtClaims<'_, PublicCovidPass> = serde_cbor::from_slice(&bytes).unwrap();
+
+        let not_before = utc_from_timestamp(1635883530).unwrap();
+        let expiry = utc_from_timestamp(1951416330).unwrap();
+
+        let second = chrono::Duration::seconds(1);
+        let none = Leeway::default();
+
+        assert_eq!(
+            claims.validate(not_before - second, none),
+            Err(CwtValidationError::NotYetActive(not_before))
+        );
+        assert_eq!(claims.validate(not_before, none), Ok(AppliedLeeway::default()));
+        assert_eq!(claims.validate(expiry - second, none), Ok(AppliedLeeway::default()));
+        assert_eq!(claims.validate(expiry, none), Err(CwtValidationError::Expired(expiry)));
+    }
+
+    #[test]
+    fn validate_version() {
+        use crate::PublicCovidPass;
+
+        let bytes = hex::decode("a501781e6469643a7765623a6e7a63702e636f76696431392e6865616c74682e6e7a051a61819a0a041a7450400a627663a46840636f6e7465787482782668747470733a2f2f7777772e77332e6f72672f323031382f63726564656e7469616c732f7631782a68747470733a2f2f6e7a63702e636f76696431392e6865616c74682e6e7a2f636f6e74657874732f76316776657273696f6e65312e302e306474797065827456657269666961626c6543726564656e7469616c6f5075626c6963436f766964506173737163726564656e7469616c5375626a656374a369676976656e4e616d65644a61636b6a66616d696c794e616d656753706172726f7763646f626a313936302d30342d3136075060a4f54d4e304332be33ad78b1eafa4b").unwrap();
+        let mut claims: CwtClaims<'_, PublicCovidPass> = serde_cbor::from_slice(&bytes).unwrap();
+        let now = utc_from_timestamp(1635883530).unwrap();
+
+        claims.verifiable_credential.version = "1.3.7";
+        assert!(claims.validate(now, Leeway::default()).is_ok());
+
+        claims.verifiable_credential.version = "2.0.0";
+        assert_eq!(
+            claims.validate(now, Leeway::default()),
+            Err(CwtValidationError::UnsupportedVersion(String::from("2.0.0")))
+        );
+
+        claims.verifiable_credential.version = "1.0";
+        assert_eq!(
+            claims.validate(now, Leeway::default()),
+            Err(CwtValidationError::MalformedVersion(String::from("1.0")))
+        );
+    }
+
+    #[test]
+    fn validate_with_leeway() {
+        use crate::PublicCovidPass;
+
+        let bytes = hex::decode("a501781e6469643a7765623a6e7a63702e636f76696431392e6865616c74682e6e7a051a61819a0a041a7450400a627663a46840636f6e7465787482782668747470733a2f2f7777772e77332e6f72672f323031382f63726564656e7469616c732f7631782a68747470733a2f2f6e7a63702e636f76696431392e6865616c74682e6e7a2f636f6e74657874732f76316776657273696f6e65312e302e306474797065827456657269666961626c6543726564656e7469616c6f5075626c6963436f766964506173737163726564656e7469616c5375626a656374a369676976656e4e616d65644a61636b6a66616d696c794e616d656753706172726f7763646f626a313936302d30342d3136075060a4f54d4e304332be33ad78b1eafa4b").unwrap();
+        let claims: CwtClaims<'_, PublicCovidPass> = serde_cbor::from_slice(&bytes).unwrap();
+
+        let not_before = utc_from_timestamp(1635883530).unwrap();
+        let expiry = utc_from_timestamp(1951416330).unwrap();
+        let minute = chrono::Duration::minutes(1);
+        let leeway = Leeway::new(minute * 5, minute * 2).unwrap();
+
+        assert_eq!(
+            claims.validate(not_before - minute * 3, leeway),
+            Ok(AppliedLeeway {
+                not_before: Some(minute * 3),
+                expiry: None
+            })
+        );
+        assert_eq!(
+            claims.validate(not_before - minute * 5, leeway),
+            Ok(AppliedLeeway {
+                not_before: Some(minute * 5),
+                expiry: None
+            })
+        );
+        assert_eq!(
+            claims.validate(not_before - minute * 6, leeway),
+            Err(CwtValidationError::NotYetActive(not_before))
+        );
+        assert_eq!(
+            claims.validate(expiry + minute, leeway),
+            Ok(AppliedLeeway {
+                not_before: None,
+                expiry: Some(minute)
+            })
+        );
+        assert_eq!(
+            claims.validate(expiry + minute * 2, leeway),
+            Err(CwtValidationError::Expired(expiry))
+        );
+
+        // saturates rather than overflowing
+        let unlimited = Leeway::new(chrono::Duration::max_value(), chrono::Duration::max_value()).unwrap();
+        assert!(claims.validate(not_before - minute * 6, unlimited).is_ok());
+        assert!(claims.validate(expiry + minute * 2, unlimited).is_ok());
+
+        assert_eq!(Leeway::new(-minute, minute), Err(LeewayError::Negative(-minute)));
+        assert_eq!(Leeway::new(minute, -minute), Err(LeewayError::Negative(-minute)));
+    }
+
+    #[test]
+    fn serialize_cbor() {
+        use chrono::NaiveDate;
+
+        use crate::PublicCovidPass;
+
+        let pass = PublicCovidPass {
+            given_name: String::from("Jack"),
+            family_name: Some(String::from("Sparrow")),
+            date_of_birth: NaiveDate::from_ymd(1960, 4, 16),
+        };
+        let claims = CwtClaims::new(
+            Uuid::parse_str("urn:uuid:60a4f54d-4e30-4332-be33-ad78b1eafa4b").unwrap(),
+            DecentralizedIdentifier::Web("nzcp.covid19.health.nz"),
+            utc_from_timestamp(1635883530).unwrap(),
+            utc_from_timestamp(1951416330).unwrap(),
+            &pass,
+        );
+
+        // the CWT claims of https://nzcp.covid19.health.nz/#valid-worked-example
+        assert_eq!(
+            hex::encode(serde_cbor::to_vec(&claims).unwrap()),
+            "a501781e6469643a7765623a6e7a63702e636f76696431392e6865616c74682e6e7a051a61819a0a041a7450400a627663a46840636f6e7465787482782668747470733a2f2f7777772e77332e6f72672f323031382f63726564656e7469616c732f7631782a68747470733a2f2f6e7a63702e636f76696431392e6865616c74682e6e7a2f636f6e74657874732f76316776657273696f6e65312e302e306474797065827456657269666961626c6543726564656e7469616c6f5075626c6963436f766964506173737163726564656e7469616c5375626a656374a369676976656e4e616d65644a61636b6a66616d696c794e616d656753706172726f7763646f626a313936302d30342d3136075060a4f54d4e304332be33ad78b1eafa4b"
+        )
+    }
+
+    #[test]
+    fn structural_errors() {
+        use serde_cbor::Value::{self, Bytes, Integer, Text};
+
+        use crate::payload::error::{from_slice, Claim, PayloadError};
+
+        // the CWT claims of https://nzcp.covid19.health.nz/#valid-worked-example, with an entry replaced
+        let claims_with = |key: Value, value: Option<Value>| {
+            let bytes = hex::decode("a501781e6469643a7765623a6e7a63702e636f76696431392e6865616c74682e6e7a051a61819a0a041a7450400a627663a46840636f6e7465787482782668747470733a2f2f7777772e77332e6f72672f323031382f63726564656e7469616c732f7631782a68747470733a2f2f6e7a63702e636f76696431392e6865616c74682e6e7a2f636f6e74657874732f76316776657273696f6e65312e302e306474797065827456657269666961626c6543726564656e7469616c6f5075626c6963436f766964506173737163726564656e7469616c5375626a656374a369676976656e4e616d65644a61636b6a66616d696c794e616d656753706172726f7763646f626a313936302d30342d3136075060a4f54d4e304332be33ad78b1eafa4b").unwrap();
+            let mut claims = match serde_cbor::from_slice(&bytes).unwrap() {
+                Value::Map(claims) => claims,
+                _ => unreachable!(),
+            };
+            match value {
+                Some(value) => claims.insert(key, value),
+                None => claims.remove(&key),
+            };
+            serde_cbor::to_vec(&claims).unwrap()
+        };
+        let error = |bytes: Vec<u8>| from_slice::<CwtClaims<'_, Value>>(&bytes).unwrap_err();
+
+        assert_eq!(
+            error(claims_with(Text(String::from("vc")), None)),
+            PayloadError::MissingClaim(Claim::VerifiableCredential)
+        );
+        assert_eq!(
+            error(claims_with(Integer(6), Some(Integer(1635883530)))),
+            PayloadError::UnknownClaim(String::from("6"))
+        );
+        assert_eq!(
+            error(claims_with(Integer(1), Some(Text(String::from("did:key:z6Mk"))))),
+            PayloadError::InvalidIssuer(String::from("did:key:z6Mk"))
+        );
+        assert_eq!(
+            error(claims_with(Integer(7), Some(Bytes(vec![0x60, 0xa4, 0xf5, 0x4d])))),
+            PayloadError::InvalidCwtTokenId
+        );
+        assert_eq!(
+            error(claims_with(Integer(4), Some(Integer(i64::MAX.into())))),
+            PayloadError::InvalidDate(Claim::Expiry)
+        );
+        assert!(matches!(
+            error(claims_with(Integer(5), Some(Text(String::from("yesterday"))))),
+            PayloadError::Cbor(_)
+        ));
+    }
 }
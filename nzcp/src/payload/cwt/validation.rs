@@ -1,10 +1,14 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use thiserror::Error;
 
 use super::{CwtClaims, DecentralizedIdentifier, VerifiableCredential};
-use crate::{pass::Pass, payload::cose::signature::verify::CoseVerificationError};
+use crate::{
+    pass::{Credential, Pass},
+    payload::{cose::signature::verify::CoseVerificationError, version::CredentialVersion},
+};
 
 #[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum CwtValidationError {
     #[error("token not yet valid as the 'not before date' is in the future (not before: {0:?})")]
     NotYetActive(DateTime<Utc>),
@@ -12,13 +16,82 @@ pub enum CwtValidationError {
     Expired(DateTime<Utc>),
     #[error("credential uses an unsupported version: {0}")]
     UnsupportedVersion(String),
+    #[error("credential version is malformed: {0:?}")]
+    MalformedVersion(String),
     #[error("credential uses invalid contexts: {0:?}")]
     InvalidContext(Vec<String>),
     #[error("credential uses invalid type: [{0:?}, {0:?}]")]
     InvalidType(String, String),
+    #[error("credential subject is invalid for the credential type {0:?}: {1}")]
+    InvalidCredentialSubject(String, String),
 }
 
-impl<'a, P: Pass> CwtClaims<'a, P> {
+/// Tolerances for clock skew between the issuer and the verifying device, applied to the not before and expiry dates.
+///
+/// The default is no leeway, matching the spec exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Leeway {
+    not_before: Duration,
+    expiry: Duration,
+}
+
+/// A leeway which would make validation stricter rather than more tolerant.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LeewayError {
+    #[error("leeway must not be negative (was {0})")]
+    Negative(Duration),
+}
+
+impl Leeway {
+    /// Accept passes whose not before date is up to `not_before` in the future, or which expired up to `expiry` ago.
+    pub fn new(not_before: Duration, expiry: Duration) -> Result<Self, LeewayError> {
+        for duration in [not_before, expiry] {
+            if duration < Duration::zero() {
+                return Err(LeewayError::Negative(duration));
+            }
+        }
+
+        Ok(Leeway { not_before, expiry })
+    }
+
+    /// How far in the future a pass's not before date can be.
+    pub fn not_before(&self) -> Duration {
+        self.not_before
+    }
+
+    /// How long ago a pass can have expired.
+    pub fn expiry(&self) -> Duration {
+        self.expiry
+    }
+}
+
+impl Default for Leeway {
+    fn default() -> Self {
+        Leeway {
+            not_before: Duration::zero(),
+            expiry: Duration::zero(),
+        }
+    }
+}
+
+/// The leeway which was needed to accept a pass, so audits can tell a pass was only accepted within the grace window.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AppliedLeeway {
+    /// How far before its not before date the pass was accepted.
+    pub not_before: Option<Duration>,
+    /// How far after its expiry the pass was accepted.
+    pub expiry: Option<Duration>,
+}
+
+impl AppliedLeeway {
+    /// Whether the pass was only accepted because of the leeway.
+    pub fn is_applied(&self) -> bool {
+        self.not_before.is_some() || self.expiry.is_some()
+    }
+}
+
+impl<'a, P: Credential> CwtClaims<'a, P> {
     /// Get the issuer of the payload, failing if it is not trusted.
     pub fn verify_issuer(
         &self,
@@ -31,48 +104,154 @@ impl<'a, P: Pass> CwtClaims<'a, P> {
             Ok(self.issuer)
         }
     }
-    pub fn validate(&self) -> Result<(), CwtValidationError> {
-        use CwtValidationError::*;
-
-        self.verifiable_credential.validate()?;
 
+    /// Validate the claims as at the given time, tolerating the given leeway.
+    pub fn validate(&self, now: DateTime<Utc>, leeway: Leeway) -> Result<AppliedLeeway, CwtValidationError> {
         // issuer would already have been verified here
-        let now = Utc::now();
-        if now < self.not_before {
-            Err(NotYetActive(self.not_before))
-        }
-        else if self.expiry <= now {
-            Err(Expired(self.expiry))
-        }
-        else {
-            Ok(())
+        self.checks(now, leeway).into_result()
+    }
+
+    /// Make every check on the claims as at the given time, rather than stopping at the first failure.
+    pub(crate) fn checks(&self, now: DateTime<Utc>, leeway: Leeway) -> ClaimChecks {
+        let credential = &self.verifiable_credential;
+
+        ClaimChecks {
+            version: validate_version(credential.version),
+            context: P::validate_context(&credential.context, credential._type.1),
+            credential_type: P::validate_type(credential._type),
+            not_before: validate_not_before(self.not_before, now, leeway.not_before),
+            expiry: validate_expiry(self.expiry, now, leeway.expiry),
         }
     }
 }
 
-const MANDATAORY_CONTEXT_URL: &'static str = "https://www.w3.org/2018/credentials/v1";
-const MANDATAORY_TYPE: &'static str = "VerifiableCredential";
+/// The outcome of each check on the claims, which [`CwtClaims::validate`] reports the first failure of.
+pub(crate) struct ClaimChecks {
+    pub(crate) version: Result<(), CwtValidationError>,
+    pub(crate) context: Result<(), CwtValidationError>,
+    pub(crate) credential_type: Result<(), CwtValidationError>,
+    pub(crate) not_before: Result<Option<Duration>, CwtValidationError>,
+    pub(crate) expiry: Result<Option<Duration>, CwtValidationError>,
+}
+
+impl ClaimChecks {
+    fn into_result(self) -> Result<AppliedLeeway, CwtValidationError> {
+        self.version?;
+        self.context?;
+        self.credential_type?;
+
+        Ok(AppliedLeeway {
+            not_before: self.not_before?,
+            expiry: self.expiry?,
+        })
+    }
+}
+
+pub(crate) const MANDATAORY_CONTEXT_URL: &str = "https://www.w3.org/2018/credentials/v1";
+pub(crate) const MANDATAORY_TYPE: &str = "VerifiableCredential";
+pub(super) const CREDENTIAL_VERSION: &str = "1.0.0";
 
 impl<'a, T> VerifiableCredential<'a, T>
 where
-    T: Pass,
+    T: Credential,
 {
     pub fn validate(&self) -> Result<(), CwtValidationError> {
-        use CwtValidationError::*;
+        validate_version(self.version)?;
+        T::validate_context(&self.context, self._type.1)?;
+        T::validate_type(self._type)
+    }
 
-        if self.version != "1.0.0" {
-            Err(UnsupportedVersion(self.version.to_owned()))
-        }
-        else if self.context.get(0) != Some(&MANDATAORY_CONTEXT_URL) || self.context.get(1) != Some(&T::CONTEXT_URL) {
-            Err(InvalidContext(
-                self.context.iter().map(|str| String::from(*str)).collect(),
-            ))
-        }
-        else if self._type != (MANDATAORY_TYPE, &T::CREDENTIAL_TYPE) {
-            Err(InvalidType(self._type.0.to_owned(), self._type.1.to_owned()))
-        }
-        else {
-            Ok(())
-        }
+    /// Get the pass, converting the credential subject for the contexts and credential type.
+    pub(crate) fn into_pass(self) -> Result<T, CwtValidationError> {
+        self.credential_subject.for_credential(&self.context, self._type.1)
+    }
+}
+
+/// Check the credential version is in one of the [supported ranges](crate::payload::version::SUPPORTED_CREDENTIAL_VERSIONS).
+pub(crate) fn validate_version(version: &str) -> Result<(), CwtValidationError> {
+    let parsed: CredentialVersion = version
+        .parse()
+        .map_err(|_| CwtValidationError::MalformedVersion(version.to_owned()))?;
+
+    if !parsed.is_supported() {
+        Err(CwtValidationError::UnsupportedVersion(version.to_owned()))
+    }
+    else {
+        Ok(())
+    }
+}
+
+pub(crate) fn validate_context<P: Pass>(context: &[&str]) -> Result<(), CwtValidationError> {
+    if context.first() != Some(&MANDATAORY_CONTEXT_URL) || context.get(1) != Some(&P::CONTEXT_URL) {
+        Err(CwtValidationError::InvalidContext(
+            context.iter().map(|str| String::from(*str)).collect(),
+        ))
+    }
+    else {
+        Ok(())
+    }
+}
+
+/// Check the contexts start with the W3C VC context, whatever the pass type.
+pub(crate) fn validate_mandatory_context(context: &[&str]) -> Result<(), CwtValidationError> {
+    if context.first() != Some(&MANDATAORY_CONTEXT_URL) {
+        Err(CwtValidationError::InvalidContext(
+            context.iter().map(|str| String::from(*str)).collect(),
+        ))
+    }
+    else {
+        Ok(())
+    }
+}
+
+/// Check the type starts with `VerifiableCredential`, whatever the pass type.
+pub(crate) fn validate_mandatory_type(_type: (&str, &str)) -> Result<(), CwtValidationError> {
+    if _type.0 != MANDATAORY_TYPE {
+        Err(CwtValidationError::InvalidType(_type.0.to_owned(), _type.1.to_owned()))
+    }
+    else {
+        Ok(())
+    }
+}
+
+pub(crate) fn validate_type<P: Pass>(_type: (&str, &str)) -> Result<(), CwtValidationError> {
+    if _type != (MANDATAORY_TYPE, P::CREDENTIAL_TYPE) {
+        Err(CwtValidationError::InvalidType(_type.0.to_owned(), _type.1.to_owned()))
+    }
+    else {
+        Ok(())
+    }
+}
+
+/// Check the pass is active as at `now`, returning how far before its not before date it was accepted, if at all.
+pub(crate) fn validate_not_before(
+    not_before: DateTime<Utc>,
+    now: DateTime<Utc>,
+    leeway: Duration,
+) -> Result<Option<Duration>, CwtValidationError> {
+    // a leeway reaching past the earliest representable date accepts any time
+    if not_before
+        .checked_sub_signed(leeway)
+        .is_some_and(|earliest| now < earliest)
+    {
+        Err(CwtValidationError::NotYetActive(not_before))
+    }
+    else {
+        Ok((now < not_before).then(|| not_before - now))
+    }
+}
+
+/// Check the pass has not expired as at `now`, returning how far after its expiry it was accepted, if at all.
+pub(crate) fn validate_expiry(
+    expiry: DateTime<Utc>,
+    now: DateTime<Utc>,
+    leeway: Duration,
+) -> Result<Option<Duration>, CwtValidationError> {
+    // a leeway reaching past the latest representable date accepts any time
+    if expiry.checked_add_signed(leeway).is_some_and(|latest| latest <= now) {
+        Err(CwtValidationError::Expired(expiry))
+    }
+    else {
+        Ok((expiry <= now).then(|| now - expiry))
     }
 }
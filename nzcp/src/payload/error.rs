@@ -0,0 +1,142 @@
+use std::fmt;
+
+use serde::{de::IgnoredAny, Deserialize};
+use thiserror::Error;
+
+use super::cose::signature::CoseSignStructureError;
+
+/// A structural defect in the CBOR payload (the COSE structure and its CWT claims).
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PayloadError {
+    /// The payload (or one of its segments) isn't valid CBOR, or a value has the wrong CBOR type.
+    #[error("invalid CBOR: {0}")]
+    Cbor(#[from] serde_cbor::Error),
+    #[error("{0}")]
+    SignStructure(#[from] CoseSignStructureError),
+    #[error("missing COSE segment: {0}")]
+    MissingCoseSegment(&'static str),
+    #[error("missing protected header: {0}")]
+    MissingProtectedHeader(ProtectedHeader),
+    #[error("unknown protected header: {0}")]
+    UnknownProtectedHeader(String),
+    #[error("unsupported signature algorithm {0} (must be ES256, ES384, ES512 or EdDSA)")]
+    UnsupportedAlgorithm(i64),
+    #[error("missing CWT claim: {0}")]
+    MissingClaim(Claim),
+    #[error("unknown CWT claim: {0}")]
+    UnknownClaim(String),
+    /// The issuer (`iss` claim) isn't a `did:web` DID.
+    #[error("invalid issuer DID '{0}' (must start with 'did:web:')")]
+    InvalidIssuer(String),
+    /// The CWT token ID (`jti` claim) isn't a 16 byte UUID.
+    #[error("invalid CWT token ID (must be a 16 byte UUID)")]
+    InvalidCwtTokenId,
+    /// A date claim is outside the range of dates which can be represented.
+    #[error("invalid date in CWT claim {0}")]
+    InvalidDate(Claim),
+}
+
+/// A protected header the spec requires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectedHeader {
+    /// The signature algorithm (`alg`).
+    Algorithm,
+    /// The key ID (`kid`).
+    KeyId,
+}
+
+impl fmt::Display for ProtectedHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProtectedHeader::Algorithm => "1 (alg)",
+            ProtectedHeader::KeyId => "4 (kid)",
+        })
+    }
+}
+
+/// A CWT claim the spec requires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Claim {
+    /// The CWT token ID (`cti`).
+    CwtTokenId,
+    /// The issuer (`iss`).
+    Issuer,
+    /// The not before date (`nbf`).
+    NotBefore,
+    /// The expiry date (`exp`).
+    Expiry,
+    /// The verifiable credential (`vc`).
+    VerifiableCredential,
+}
+
+impl fmt::Display for Claim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Claim::CwtTokenId => "7 (cti)",
+            Claim::Issuer => "1 (iss)",
+            Claim::NotBefore => "5 (nbf)",
+            Claim::Expiry => "4 (exp)",
+            Claim::VerifiableCredential => "vc",
+        })
+    }
+}
+
+impl PartialEq for PayloadError {
+    fn eq(&self, other: &Self) -> bool {
+        use PayloadError::*;
+        match (self, other) {
+            (Cbor(l0), Cbor(r0)) => l0.to_string() == r0.to_string(),
+            (SignStructure(l0), SignStructure(r0)) => l0 == r0,
+            (MissingCoseSegment(l0), MissingCoseSegment(r0)) => l0 == r0,
+            (MissingProtectedHeader(l0), MissingProtectedHeader(r0)) => l0 == r0,
+            (UnknownProtectedHeader(l0), UnknownProtectedHeader(r0)) => l0 == r0,
+            (UnsupportedAlgorithm(l0), UnsupportedAlgorithm(r0)) => l0 == r0,
+            (MissingClaim(l0), MissingClaim(r0)) => l0 == r0,
+            (UnknownClaim(l0), UnknownClaim(r0)) => l0 == r0,
+            (InvalidIssuer(l0), InvalidIssuer(r0)) => l0 == r0,
+            (InvalidCwtTokenId, InvalidCwtTokenId) => true,
+            (InvalidDate(l0), InvalidDate(r0)) => l0 == r0,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for PayloadError {}
+
+/// The outcome of deserializing a payload structure.
+///
+/// Serde errors can only carry a message, so the visitors return structural defects as the value and leave serde
+/// errors for invalid CBOR.
+pub(crate) struct Checked<T>(pub(crate) Result<T, PayloadError>);
+
+/// Deserialize a payload structure from CBOR, keeping structural defects typed.
+pub(crate) fn from_slice<'a, T>(bytes: &'a [u8]) -> Result<T, PayloadError>
+where
+    Checked<T>: Deserialize<'a>,
+{
+    serde_cbor::from_slice::<Checked<T>>(bytes)?.0
+}
+
+/// Describe an unexpected map key for an error.
+pub(crate) fn describe_key(key: &serde_cbor::Value) -> String {
+    match key {
+        serde_cbor::Value::Integer(key) => key.to_string(),
+        serde_cbor::Value::Text(key) => format!("{:?}", key),
+        key => format!("{:?}", key),
+    }
+}
+
+/// Skip the value of an unexpected map entry, recording the first defect found.
+pub(crate) fn skip_value<'de, A>(
+    map: &mut A,
+    error: &mut Option<PayloadError>,
+    defect: PayloadError,
+) -> Result<(), A::Error>
+where
+    A: serde::de::MapAccess<'de>,
+{
+    map.next_value::<IgnoredAny>()?;
+    error.get_or_insert(defect);
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! The payload versions this crate can verify: the barcode version identifier (`NZCP:/1/...`) and the verifiable
+//! credential version (`vc.version`, e.g. `1.0.0`).
+//!
+//! Versions outside these are rejected as unsupported rather than malformed, so passes in a format rolled out after
+//! this crate was released can be told apart from broken barcodes.
+
+use std::{fmt, str::FromStr};
+
+/// The barcode version identifiers which can be decoded.
+pub const SUPPORTED_BARCODE_VERSIONS: [u32; 1] = [1];
+
+/// The credential versions which can be validated. Minor and patch versions are backwards compatible, so any 1.x.y
+/// credential is accepted.
+pub const SUPPORTED_CREDENTIAL_VERSIONS: [CredentialVersionRange; 1] = [CredentialVersionRange::major(1)];
+
+/// A verifiable credential version, `MAJOR.MINOR.PATCH` as in [semver](https://semver.org) but without pre-release
+/// or build metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CredentialVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl CredentialVersion {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        CredentialVersion { major, minor, patch }
+    }
+
+    /// Whether a range in [`SUPPORTED_CREDENTIAL_VERSIONS`] contains this version.
+    pub fn is_supported(&self) -> bool {
+        SUPPORTED_CREDENTIAL_VERSIONS.iter().any(|range| range.contains(*self))
+    }
+}
+
+/// The version isn't `MAJOR.MINOR.PATCH`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MalformedVersion;
+
+impl FromStr for CredentialVersion {
+    type Err = MalformedVersion;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let mut parts = string.split('.').map(parse_version_number);
+
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(major), Some(minor), Some(patch), None) => Ok(CredentialVersion::new(major?, minor?, patch?)),
+            _ => Err(MalformedVersion),
+        }
+    }
+}
+
+impl fmt::Display for CredentialVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A number in a version, which can't have a sign or leading zeros.
+pub(crate) fn parse_version_number<T: FromStr>(string: &str) -> Result<T, MalformedVersion> {
+    let digits = !string.is_empty() && string.bytes().all(|byte| byte.is_ascii_digit());
+    if !digits || (string.len() > 1 && string.starts_with('0')) {
+        return Err(MalformedVersion);
+    }
+
+    string.parse().map_err(|_| MalformedVersion)
+}
+
+/// A range of credential versions, from `min` up to but not including `max`, or with no upper bound if `max` is `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CredentialVersionRange {
+    pub min: CredentialVersion,
+    pub max: Option<CredentialVersion>,
+}
+
+impl CredentialVersionRange {
+    /// Every version with the given major version, i.e. semver's `^MAJOR.0.0`.
+    pub const fn major(major: u64) -> Self {
+        CredentialVersionRange {
+            min: CredentialVersion::new(major, 0, 0),
+            // the last major version has no next one to stop before
+            max: match major.checked_add(1) {
+                Some(next) => Some(CredentialVersion::new(next, 0, 0)),
+                None => None,
+            },
+        }
+    }
+
+    pub fn contains(&self, version: CredentialVersion) -> bool {
+        self.min <= version && self.max.is_none_or(|max| version < max)
+    }
+}
+
+impl fmt::Display for CredentialVersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, ">={}", self.min)?;
+        match self.max {
+            Some(max) => write!(f, ", <{}", max),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_credential_version() {
+        assert_eq!("1.0.0".parse(), Ok(CredentialVersion::new(1, 0, 0)));
+        assert_eq!("1.12.3".parse(), Ok(CredentialVersion::new(1, 12, 3)));
+
+        for malformed in [
+            "",
+            "1",
+            "1.0",
+            "1.0.0.0",
+            "01.0.0",
+            "1.0.0-beta",
+            "1.+0.0",
+            "v1.0.0",
+            "1..0",
+        ] {
+            assert_eq!(
+                malformed.parse::<CredentialVersion>(),
+                Err(MalformedVersion),
+                "{}",
+                malformed
+            );
+        }
+    }
+
+    #[test]
+    fn supported_credential_versions() {
+        assert!(CredentialVersion::new(1, 0, 0).is_supported());
+        assert!(CredentialVersion::new(1, 4, 2).is_supported());
+        assert!(!CredentialVersion::new(0, 9, 0).is_supported());
+        assert!(!CredentialVersion::new(2, 0, 0).is_supported());
+
+        assert_eq!(CredentialVersionRange::major(1).to_string(), ">=1.0.0, <2.0.0");
+
+        let last = CredentialVersionRange::major(u64::MAX);
+        assert!(last.contains(CredentialVersion::new(u64::MAX, u64::MAX, u64::MAX)));
+        assert!(!last.contains(CredentialVersion::new(u64::MAX - 1, 0, 0)));
+        assert_eq!(last.to_string(), format!(">={}.0.0", u64::MAX));
+    }
+}
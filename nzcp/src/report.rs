@@ -0,0 +1,590 @@
+use std::{fmt, marker::PhantomData};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    decentralised_identifier::{DecentralizedIdentifier, DecentralizedIdentifierError},
+    error::QrBarcodeError,
+    pass::Credential,
+    payload::{
+        barcode::{QrBarcode, NZCP_PREFIX},
+        cose::{
+            self,
+            signature::{verify::VerifyingKey, CoseSignStructure, CoseSignature, SignatureAlgorithm},
+        },
+        cwt::{
+            validation::{ClaimChecks, Leeway},
+            CwtClaims,
+        },
+        error::{Claim, PayloadError, ProtectedHeader},
+    },
+};
+
+/// A check made while verifying a pass, in the order they are made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Check {
+    /// The barcode starts with `NZCP:/`.
+    BarcodePrefix,
+    /// The barcode's version identifier is `1`.
+    BarcodeVersion,
+    /// The barcode's payload is base32 encoded.
+    BarcodeEncoding,
+    /// The payload is a CBOR COSE structure with the protected headers and CWT claims the spec requires, and no others.
+    CoseStructure,
+    /// The COSE structure is tagged as `COSE_Sign1`.
+    CoseTag,
+    /// The signature algorithm is allowed, by default only ES256.
+    Algorithm,
+    /// The issuer is a trusted `did:web` DID.
+    Issuer,
+    /// The issuer's verifying key for the key ID was found.
+    KeyResolution,
+    /// The signature is valid for the verifying key.
+    Signature,
+    /// The credential version is supported.
+    CredentialVersion,
+    /// The credential's JSON-LD contexts match the pass type.
+    CredentialContext,
+    /// The credential's type matches the pass type.
+    CredentialType,
+    /// The credential subject is a valid pass.
+    CredentialSubject,
+    /// The pass is active.
+    NotBefore,
+    /// The pass has not expired.
+    Expiry,
+}
+
+impl Check {
+    pub const ALL: [Check; 15] = [
+        Check::BarcodePrefix,
+        Check::BarcodeVersion,
+        Check::BarcodeEncoding,
+        Check::CoseStructure,
+        Check::CoseTag,
+        Check::Algorithm,
+        Check::Issuer,
+        Check::KeyResolution,
+        Check::Signature,
+        Check::CredentialVersion,
+        Check::CredentialContext,
+        Check::CredentialType,
+        Check::CredentialSubject,
+        Check::NotBefore,
+        Check::Expiry,
+    ];
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Check::BarcodePrefix => "barcode prefix",
+            Check::BarcodeVersion => "barcode version",
+            Check::BarcodeEncoding => "barcode encoding",
+            Check::CoseStructure => "COSE structure",
+            Check::CoseTag => "COSE tag",
+            Check::Algorithm => "signature algorithm",
+            Check::Issuer => "issuer",
+            Check::KeyResolution => "key resolution",
+            Check::Signature => "signature",
+            Check::CredentialVersion => "credential version",
+            Check::CredentialContext => "credential context",
+            Check::CredentialType => "credential type",
+            Check::CredentialSubject => "credential subject",
+            Check::NotBefore => "not before",
+            Check::Expiry => "expiry",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// The check couldn't be made as an earlier check failed.
+    Skipped,
+}
+
+/// The outcome of a single check, with details of what was checked, why it failed or why it was skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub check: Check,
+    pub status: CheckStatus,
+    pub details: String,
+}
+
+/// The outcome of every check made while verifying a pass, rather than only the first failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl VerificationReport {
+    /// Whether every check passed, i.e. the pass is valid.
+    pub fn is_valid(&self) -> bool {
+        !self.checks.is_empty() && self.checks.iter().all(|result| result.status == CheckStatus::Passed)
+    }
+
+    pub fn get(&self, check: Check) -> Option<&CheckResult> {
+        self.checks.iter().find(|result| result.check == check)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|result| result.status == CheckStatus::Failed)
+    }
+}
+
+impl fmt::Display for VerificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.checks {
+            let status = match result.status {
+                CheckStatus::Passed => "PASS",
+                CheckStatus::Failed => "FAIL",
+                CheckStatus::Skipped => "SKIP",
+            };
+            writeln!(f, "[{}] {}: {}", status, result.check, result.details)?;
+        }
+        Ok(())
+    }
+}
+
+/// A report which has made every check but the signature check, so the issuer's verifying key can then be resolved
+/// either asynchronously or synchronously.
+///
+/// Each check is the outcome of the same typed step verification makes, so a report is valid iff verification
+/// succeeds.
+pub(crate) struct PendingReport<P> {
+    checks: Vec<CheckResult>,
+    /// The reason every remaining check is skipped, after a failure which stops any further checks.
+    stopped: Option<String>,
+    signature: Option<SignedSegments>,
+    key_to_resolve: Option<(String, String)>,
+    pass: PhantomData<fn() -> P>,
+}
+
+/// The signature and the raw segments it signs, which are needed to check the signature once the key is resolved.
+struct SignedSegments {
+    algorithm: SignatureAlgorithm,
+    protected_headers: Vec<u8>,
+    cwt_claims: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl<P: Credential> PendingReport<P> {
+    /// Make the checks on the barcode, COSE structure, issuer and claims, validating the claims as at `now`.
+    pub(crate) fn new(
+        barcode_str: &str,
+        trusted_issuers: &[DecentralizedIdentifier<'_>],
+        allowed_algorithms: &[SignatureAlgorithm],
+        now: DateTime<Utc>,
+        leeway: Leeway,
+    ) -> Self {
+        let mut report = PendingReport {
+            checks: Vec::with_capacity(Check::ALL.len()),
+            stopped: None,
+            signature: None,
+            key_to_resolve: None,
+            pass: PhantomData,
+        };
+        if let Some(barcode) = report.check_barcode(barcode_str) {
+            report.check_cose(&barcode.cbor, trusted_issuers, allowed_algorithms, now, leeway);
+        }
+        report
+    }
+
+    /// The issuer and key ID to resolve the verifying key for, if the checks so far allow it.
+    pub(crate) fn key_to_resolve(&self) -> Option<(DecentralizedIdentifier<'_>, &str)> {
+        let (issuer, kid) = self.key_to_resolve.as_ref()?;
+        Some((DecentralizedIdentifier::parse(issuer)?, kid))
+    }
+
+    /// Check the signature, given the outcome of resolving the verifying key (if it was resolved).
+    pub(crate) fn finish(
+        mut self,
+        verifying_key: Option<Result<VerifyingKey, DecentralizedIdentifierError>>,
+    ) -> VerificationReport {
+        if self.stopped.is_none() {
+            self.check_signature(verifying_key);
+        }
+
+        // skip anything which couldn't be checked
+        let reason = self.stopped.take().unwrap_or_default();
+        for check in Check::ALL {
+            if self.checks.iter().all(|result| result.check != check) {
+                self.skip(check, &reason);
+            }
+        }
+        self.checks
+            .sort_by_key(|result| Check::ALL.iter().position(|check| *check == result.check));
+
+        VerificationReport { checks: self.checks }
+    }
+
+    fn pass(&mut self, check: Check, details: impl Into<String>) {
+        self.record(check, CheckStatus::Passed, details.into());
+    }
+
+    fn fail(&mut self, check: Check, details: impl ToString) {
+        self.record(check, CheckStatus::Failed, details.to_string());
+    }
+
+    fn skip(&mut self, check: Check, reason: &str) {
+        self.record(check, CheckStatus::Skipped, reason.to_owned());
+    }
+
+    fn record(&mut self, check: Check, status: CheckStatus, details: String) {
+        self.checks.push(CheckResult { check, status, details });
+    }
+
+    /// Fail a check which stops any further checks.
+    fn stop(&mut self, check: Check, details: impl ToString) {
+        self.fail(check, details);
+        self.stopped = Some(format!("the {} check failed", check));
+    }
+
+    fn check_barcode(&mut self, barcode_str: &str) -> Option<QrBarcode> {
+        use Check::*;
+
+        let barcode: QrBarcode = match barcode_str.parse() {
+            Ok(barcode) => barcode,
+            Err(error) => {
+                // the prefix is parsed before the version, and the version before the payload, so leading
+                // whitespace and lower case in the prefix are the only errors in the prefix
+                let check = match error {
+                    QrBarcodeError::MissingNzcpPrefix | QrBarcodeError::Whitespace(0) => BarcodePrefix,
+                    QrBarcodeError::Lowercase(position) if position < NZCP_PREFIX.len() => BarcodePrefix,
+                    QrBarcodeError::MalformedVersion | QrBarcodeError::UnsupportedVersion(_) => BarcodeVersion,
+                    QrBarcodeError::InvalidBase32Character(..)
+                    | QrBarcodeError::InvalidBase32Length(_)
+                    | QrBarcodeError::Base32Padding(_)
+                    | QrBarcodeError::Lowercase(_)
+                    | QrBarcodeError::Whitespace(_) => BarcodeEncoding,
+                };
+                if check != BarcodePrefix {
+                    self.pass(BarcodePrefix, "NZCP:/");
+                }
+                if check == BarcodeEncoding {
+                    // only supported versions get as far as decoding the payload
+                    self.pass(
+                        BarcodeVersion,
+                        barcode_str
+                            .strip_prefix(NZCP_PREFIX)
+                            .and_then(|rest| rest.split('/').next())
+                            .unwrap_or_default(),
+                    );
+                }
+                self.stop(check, error);
+                return None;
+            }
+        };
+        self.pass(BarcodePrefix, "NZCP:/");
+        self.pass(BarcodeVersion, barcode.version.to_string());
+        self.pass(BarcodeEncoding, format!("{} bytes of CBOR", barcode.cbor.len()));
+
+        Some(barcode)
+    }
+
+    fn check_cose(
+        &mut self,
+        cbor: &[u8],
+        trusted_issuers: &[DecentralizedIdentifier<'_>],
+        allowed_algorithms: &[SignatureAlgorithm],
+        now: DateTime<Utc>,
+        leeway: Leeway,
+    ) {
+        let structure = match cose::CoseStructure::<P>::from_slice(cbor) {
+            Ok(structure) => structure,
+            Err(error) => return self.stop(failed_check(&error), error),
+        };
+        self.pass(
+            Check::CoseStructure,
+            "COSE structure with protected headers, CWT claims and signature",
+        );
+        self.pass(Check::CoseTag, "COSE_Sign1");
+
+        let algorithm = structure.protected_headers().algorithm;
+        match structure.verify_algorithm(allowed_algorithms) {
+            Ok(()) => self.pass(Check::Algorithm, algorithm.to_string()),
+            Err(error) => {
+                let allowed = allowed_algorithms
+                    .iter()
+                    .map(|algorithm| format!("{} ({})", algorithm, i8::from(algorithm)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                self.fail(Check::Algorithm, format!("{}, must be {}", error, allowed));
+            }
+        }
+
+        match structure.cwt_claims().verify_issuer(trusted_issuers) {
+            Ok(issuer) => {
+                self.pass(Check::Issuer, issuer.did());
+                self.key_to_resolve = Some((issuer.did(), structure.kid().to_owned()));
+            }
+            Err(error) => {
+                self.fail(Check::Issuer, error);
+                self.skip(Check::KeyResolution, "the issuer is not trusted");
+            }
+        }
+
+        let signature = structure.signature();
+        self.signature = Some(SignedSegments {
+            algorithm,
+            protected_headers: signature.protected_headers_raw.to_vec(),
+            cwt_claims: signature.cwt_claims_raw.to_vec(),
+            signature: signature.bytes.to_vec(),
+        });
+
+        self.check_claims(structure.into_cwt_claims(), now, leeway);
+    }
+
+    fn check_signature(&mut self, verifying_key: Option<Result<VerifyingKey, DecentralizedIdentifierError>>) {
+        use Check::*;
+
+        let verifying_key = match verifying_key {
+            Some(Ok(verifying_key)) => {
+                if let Some((issuer, kid)) = self.key_to_resolve.clone() {
+                    self.pass(KeyResolution, format!("{}#{}", issuer, kid));
+                }
+                verifying_key
+            }
+            Some(Err(error)) => {
+                self.fail(KeyResolution, error);
+                return self.skip(Signature, "the verifying key could not be resolved");
+            }
+            None => return self.skip(Signature, "the verifying key could not be resolved"),
+        };
+
+        if let Some(segments) = &self.signature {
+            let signature = CoseSignature {
+                bytes: &segments.signature,
+                sign_structure: CoseSignStructure::Sign1,
+                protected_headers_raw: &segments.protected_headers,
+                cwt_claims_raw: &segments.cwt_claims,
+            };
+
+            match signature.verify(segments.algorithm, &verifying_key) {
+                Ok(()) => self.pass(Signature, format!("valid {} signature", segments.algorithm)),
+                Err(error) => self.fail(Signature, error),
+            }
+        }
+    }
+
+    fn check_claims(&mut self, claims: CwtClaims<'_, P>, now: DateTime<Utc>, leeway: Leeway) {
+        use Check::*;
+
+        let ClaimChecks {
+            version,
+            context,
+            credential_type,
+            not_before,
+            expiry,
+        } = claims.checks(now, leeway);
+        let credential = claims.verifiable_credential();
+
+        match version {
+            Ok(()) => self.pass(CredentialVersion, credential.version()),
+            Err(error) => self.fail(CredentialVersion, error),
+        }
+
+        match context {
+            Ok(()) => self.pass(CredentialContext, credential.context().join(", ")),
+            Err(error) => self.fail(CredentialContext, error),
+        }
+
+        let (first, second) = credential.credential_type();
+        match credential_type {
+            Ok(()) => self.pass(CredentialType, format!("{}, {}", first, second)),
+            Err(error) => self.fail(CredentialType, error),
+        }
+
+        let (not_before_date, expiry_date) = (claims.not_before(), claims.expiry());
+        match claims.verifiable_credential.into_pass() {
+            Ok(_) => self.pass(CredentialSubject, second),
+            Err(error) => self.fail(CredentialSubject, error),
+        }
+
+        match not_before {
+            Ok(None) => self.pass(NotBefore, format!("active since {}", not_before_date)),
+            Ok(Some(applied)) => self.pass(
+                NotBefore,
+                format!(
+                    "active from {}, accepted {} early within the leeway",
+                    not_before_date, applied
+                ),
+            ),
+            Err(error) => self.fail(NotBefore, error),
+        }
+
+        match expiry {
+            Ok(None) => self.pass(Expiry, format!("valid until {}", expiry_date)),
+            Ok(Some(applied)) => self.pass(
+                Expiry,
+                format!(
+                    "expired at {}, accepted {} late within the leeway",
+                    expiry_date, applied
+                ),
+            ),
+            Err(error) => self.fail(Expiry, error),
+        }
+    }
+}
+
+/// The check a structural defect fails, which is the COSE structure check unless a more specific check covers it.
+fn failed_check(error: &PayloadError) -> Check {
+    match error {
+        PayloadError::SignStructure(_) => Check::CoseTag,
+        PayloadError::UnsupportedAlgorithm(_) | PayloadError::MissingProtectedHeader(ProtectedHeader::Algorithm) => {
+            Check::Algorithm
+        }
+        PayloadError::MissingProtectedHeader(ProtectedHeader::KeyId) => Check::KeyResolution,
+        PayloadError::MissingClaim(Claim::Issuer) | PayloadError::InvalidIssuer(_) => Check::Issuer,
+        PayloadError::MissingClaim(Claim::NotBefore) | PayloadError::InvalidDate(Claim::NotBefore) => Check::NotBefore,
+        PayloadError::MissingClaim(Claim::Expiry) | PayloadError::InvalidDate(Claim::Expiry) => Check::Expiry,
+        _ => Check::CoseStructure,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDateTime, TimeZone};
+
+    use super::*;
+    use crate::{payload::cose::signature::NZCP_ALGORITHMS, PublicCovidPass, TrustStore};
+
+    const EXAMPLE_ISSUER: DecentralizedIdentifier<'static> = DecentralizedIdentifier::Web("nzcp.covid19.health.nz");
+
+    fn report(barcode: &str, trusted_issuers: &[DecentralizedIdentifier<'_>]) -> VerificationReport {
+        let mut trust_store = TrustStore::new();
+        trust_store
+            .add_document_json(include_str!("../tests/did/nzcp.covid19.health.nz.json"))
+            .unwrap();
+
+        // when the spec examples were published
+        let now = DateTime::from_utc(NaiveDateTime::from_timestamp(1635897600, 0), Utc);
+        let report =
+            PendingReport::<PublicCovidPass>::new(barcode, trusted_issuers, &NZCP_ALGORITHMS, now, Leeway::default());
+        let verifying_key = report
+            .key_to_resolve()
+            .map(|(issuer, kid)| trust_store.verifying_key(issuer, kid));
+
+        report.finish(verifying_key)
+    }
+
+    fn statuses(report: &VerificationReport) -> Vec<(Check, CheckStatus)> {
+        report
+            .checks
+            .iter()
+            .map(|result| (result.check, result.status))
+            .collect()
+    }
+
+    #[test]
+    fn valid_pass() {
+        // https://nzcp.covid19.health.nz/#valid-worked-example
+        let report = report("NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX", &[EXAMPLE_ISSUER]);
+
+        assert!(report.is_valid(), "{}", report);
+        assert_eq!(
+            statuses(&report),
+            Check::ALL
+                .iter()
+                .map(|check| (*check, CheckStatus::Passed))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            report.get(Check::KeyResolution).unwrap().details,
+            "did:web:nzcp.covid19.health.nz#key-1"
+        );
+    }
+
+    #[test]
+    fn expired_pass_with_bad_signature() {
+        // https://nzcp.covid19.health.nz/#expired-pass, with the last byte of the signature changed
+        let barcode = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUX5AM2FQIGTBPBPYWYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVA56TNJCCUN2NVK5NGAYOZ6VIWACYIBM3QXW7SLCMD2WTJ3GSEI5JH7RXAEURGATOHAHXC2O6BEJKBSVI25ICTBR5SFYUDSVLB2F6SJ63LWJ6Z3FWNHOXF6A2QLJNUFRQNTRA";
+        let report = report(barcode, &[EXAMPLE_ISSUER]);
+
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.failures().map(|result| result.check).collect::<Vec<_>>(),
+            vec![Check::Signature, Check::Expiry]
+        );
+        assert_eq!(
+            report.get(Check::Expiry).unwrap().details,
+            format!("token has expired (expired: {:?})", Utc.timestamp(1635278731, 0))
+        );
+    }
+
+    #[test]
+    fn untrusted_issuer() {
+        let report = report("NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX", &[]);
+
+        assert_eq!(report.get(Check::Issuer).unwrap().status, CheckStatus::Failed);
+        assert_eq!(report.get(Check::KeyResolution).unwrap().status, CheckStatus::Skipped);
+        assert_eq!(report.get(Check::Signature).unwrap().status, CheckStatus::Skipped);
+        assert_eq!(report.get(Check::Expiry).unwrap().status, CheckStatus::Passed);
+    }
+
+    #[test]
+    fn invalid_barcode() {
+        let report = report("NZCP:/2/2KCEVIQEIVVWK6JNGEASNICZAEP2KALY", &[EXAMPLE_ISSUER]);
+
+        let mut expected = vec![
+            (Check::BarcodePrefix, CheckStatus::Passed),
+            (Check::BarcodeVersion, CheckStatus::Failed),
+        ];
+        expected.extend(Check::ALL[2..].iter().map(|check| (*check, CheckStatus::Skipped)));
+        assert_eq!(statuses(&report), expected);
+        assert_eq!(
+            report.get(Check::Signature).unwrap().details,
+            "the barcode version check failed"
+        );
+    }
+
+    #[test]
+    fn not_a_barcode() {
+        for barcode in ["hello world \n", " NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALY"] {
+            let report = report(barcode, &[EXAMPLE_ISSUER]);
+
+            let mut expected = vec![(Check::BarcodePrefix, CheckStatus::Failed)];
+            expected.extend(Check::ALL[1..].iter().map(|check| (*check, CheckStatus::Skipped)));
+            assert_eq!(statuses(&report), expected, "{}", barcode);
+        }
+
+        let report = report("NZCP:/v1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALY \n", &[EXAMPLE_ISSUER]);
+        assert_eq!(report.get(Check::BarcodePrefix).unwrap().status, CheckStatus::Passed);
+        assert_eq!(report.get(Check::BarcodeVersion).unwrap().status, CheckStatus::Failed);
+    }
+
+    #[test]
+    fn multibyte_characters() {
+        let in_prefix = report("aéééééé \n", &[EXAMPLE_ISSUER]);
+        assert_eq!(in_prefix.get(Check::BarcodePrefix).unwrap().status, CheckStatus::Failed);
+
+        let in_payload = report("NZCP:/1/ééé \n", &[EXAMPLE_ISSUER]);
+        assert_eq!(in_payload.get(Check::BarcodeVersion).unwrap().details, "1");
+        assert_eq!(
+            in_payload.get(Check::BarcodeEncoding).unwrap().status,
+            CheckStatus::Failed
+        );
+    }
+
+    #[test]
+    fn non_normalised_barcode() {
+        let lowercase = report("nzcp:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALY", &[EXAMPLE_ISSUER]);
+        assert_eq!(lowercase.get(Check::BarcodePrefix).unwrap().status, CheckStatus::Failed);
+
+        let trailing_newline = report("NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALY\n", &[EXAMPLE_ISSUER]);
+        let mut expected = vec![
+            (Check::BarcodePrefix, CheckStatus::Passed),
+            (Check::BarcodeVersion, CheckStatus::Passed),
+            (Check::BarcodeEncoding, CheckStatus::Failed),
+        ];
+        expected.extend(Check::ALL[3..].iter().map(|check| (*check, CheckStatus::Skipped)));
+        assert_eq!(statuses(&trailing_newline), expected);
+        assert_eq!(
+            trailing_newline.get(Check::BarcodeEncoding).unwrap().details,
+            "The QR Code MUST NOT contain whitespace: whitespace at position 40"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! Support for testing the whole verification pipeline offline: issuers which sign passes with generated keys, and an
+//! in-process mock `did:web` server publishing their DID documents. Enabled by the `test-utils` feature.
+//!
+//! ```ignore
+//! use nzcp::test_utils::{MockDidServer, TestIssuer};
+//!
+//! let issuer = TestIssuer::new("issuer.test");
+//! let server = MockDidServer::start().await;
+//! server.publish(&issuer);
+//!
+//! let barcode = issuer.issue_valid_pass_uri(&pass);
+//! let verifier = server.verifier([issuer.identifier()]).build();
+//! let verified = verifier.verify::<PublicCovidPass>(&barcode).await?;
+//! ```
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use p256::ecdsa::SigningKey;
+use rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use serde_json::json;
+use ssi::jwk::{Base64urlUInt, ECParams, Params, JWK};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::{
+    decentralised_identifier::resolver::DidWebProxyResolver, pass::Pass, DecentralizedIdentifier, PassIssuer, Verifier,
+    VerifierBuilder,
+};
+
+/// A pass issuer for `did:web:<domain>`, signing passes with generated P-256 keys.
+#[derive(Debug, Clone)]
+pub struct TestIssuer {
+    domain: String,
+    keys: Vec<(String, SigningKey)>,
+}
+
+impl TestIssuer {
+    /// An issuer with a single generated key, `key-1`.
+    pub fn new(domain: impl Into<String>) -> Self {
+        TestIssuer {
+            domain: domain.into(),
+            keys: Vec::new(),
+        }
+        .with_key("key-1")
+    }
+
+    /// Generate another key, e.g. to test key rotation.
+    pub fn with_key(mut self, kid: impl Into<String>) -> Self {
+        self.keys.push((kid.into(), SigningKey::random(&mut OsRng)));
+        self
+    }
+
+    /// The issuer's DID, e.g. `did:web:issuer.test`.
+    pub fn did(&self) -> String {
+        self.identifier().did()
+    }
+
+    pub fn identifier(&self) -> DecentralizedIdentifier<'_> {
+        DecentralizedIdentifier::Web(&self.domain)
+    }
+
+    /// The signing key for the key ID, if the issuer has it.
+    pub fn signing_key(&self, kid: &str) -> Option<&SigningKey> {
+        self.keys
+            .iter()
+            .find_map(|(key_id, signing_key)| (key_id == kid).then_some(signing_key))
+    }
+
+    /// The DID document, publishing every key as a `JsonWebKey2020` verification method and an assertion method.
+    pub fn did_document(&self) -> serde_json::Value {
+        let did = self.did();
+        let key_id = |kid: &str| format!("{}#{}", did, kid);
+
+        let verification_methods: Vec<_> = self
+            .keys
+            .iter()
+            .map(|(kid, signing_key)| {
+                // an uncompressed SEC1 point is 0x04 then the x and y coordinates
+                let point = signing_key.verifying_key().to_encoded_point(false);
+                let (x, y) = point.as_bytes()[1..].split_at(32);
+                let jwk = JWK::from(Params::EC(ECParams {
+                    curve: Some(String::from("P-256")),
+                    x_coordinate: Some(Base64urlUInt(x.to_vec())),
+                    y_coordinate: Some(Base64urlUInt(y.to_vec())),
+                    ecc_private_key: None,
+                }));
+
+                json!({
+                    "id": key_id(kid),
+                    "controller": did,
+                    "type": "JsonWebKey2020",
+                    "publicKeyJwk": jwk,
+                })
+            })
+            .collect();
+        let assertion_methods: Vec<_> = self.keys.iter().map(|(kid, _)| key_id(kid)).collect();
+
+        json!({
+            "id": did,
+            "@context": ["https://www.w3.org/ns/did/v1", "https://w3id.org/security/suites/jws-2020/v1"],
+            "verificationMethod": verification_methods,
+            "assertionMethod": assertion_methods,
+        })
+    }
+
+    /// Sign a pass with the first key and a random CWT token ID.
+    ///
+    /// Panics if the expiry isn't after the not before date.
+    pub fn issue_pass_uri<P: Pass + Serialize>(
+        &self,
+        pass: &P,
+        not_before: DateTime<Utc>,
+        expiry: DateTime<Utc>,
+    ) -> String {
+        self.issue_pass_uri_with_key(&self.keys[0].0, pass, not_before, expiry)
+    }
+
+    /// Sign a pass with the key, which needn't be in the DID document (e.g. to test unknown keys).
+    ///
+    /// Panics if the issuer doesn't have the key, or the expiry isn't after the not before date.
+    pub fn issue_pass_uri_with_key<P: Pass + Serialize>(
+        &self,
+        kid: &str,
+        pass: &P,
+        not_before: DateTime<Utc>,
+        expiry: DateTime<Utc>,
+    ) -> String {
+        let signing_key = self.signing_key(kid).expect("the issuer doesn't have the key").clone();
+        let mut cwt_token_id = [0; 16];
+        OsRng.fill_bytes(&mut cwt_token_id);
+
+        PassIssuer::new(self.identifier(), kid, signing_key)
+            .issue_pass_uri(pass, Uuid::from_bytes(cwt_token_id), not_before, expiry)
+            .expect("the validity period is invalid")
+    }
+
+    /// Sign a pass which became active yesterday and expires in a year.
+    pub fn issue_valid_pass_uri<P: Pass + Serialize>(&self, pass: &P) -> String {
+        let now = Utc::now();
+
+        self.issue_pass_uri(pass, now - Duration::days(1), now + Duration::days(365))
+    }
+}
+
+/// An in-process HTTP server publishing DID documents, for resolving with a [`DidWebProxyResolver`].
+///
+/// The document for `did:web:<domain>` is served at `/<domain>/.well-known/did.json`. The server stops when dropped.
+#[derive(Debug)]
+pub struct MockDidServer {
+    url: String,
+    documents: Arc<RwLock<HashMap<String, String>>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockDidServer {
+    /// Start the server on a free local port. Must be called within a Tokio runtime.
+    pub async fn start() -> Self {
+        let documents: Arc<RwLock<HashMap<String, String>>> = Arc::default();
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+
+        let served_documents = documents.clone();
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service_fn(move |_| {
+            let documents = served_documents.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let document = documents
+                        .read()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .get(request.uri().path().trim_start_matches('/'))
+                        .cloned();
+
+                    async move {
+                        let response = match document {
+                            Some(document) => Response::new(Body::from(document)),
+                            None => Response::builder()
+                                .status(StatusCode::NOT_FOUND)
+                                .body(Body::empty())
+                                .expect("responses are valid"),
+                        };
+                        Ok::<_, Infallible>(response)
+                    }
+                }))
+            }
+        }));
+
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server.with_graceful_shutdown(async {
+            shutdown_signal.await.ok();
+        }));
+
+        MockDidServer {
+            url,
+            documents,
+            shutdown: Some(shutdown),
+        }
+    }
+
+    /// The base URL of the server, e.g. `http://127.0.0.1:49152`.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Publish the issuer's DID document, replacing any previous document.
+    pub fn publish(&self, issuer: &TestIssuer) {
+        self.publish_document(issuer.identifier(), issuer.did_document().to_string());
+    }
+
+    /// Publish a DID document, e.g. the spec's example document to verify its example passes offline.
+    pub fn publish_document(&self, did: DecentralizedIdentifier<'_>, document: impl Into<String>) {
+        self.documents
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(did.document_path(), document.into());
+    }
+
+    /// Stop publishing the DID document, so resolving it fails.
+    pub fn unpublish(&self, did: DecentralizedIdentifier<'_>) {
+        self.documents
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&did.document_path());
+    }
+
+    /// A resolver fetching DID documents from this server.
+    pub fn resolver(&self) -> DidWebProxyResolver {
+        DidWebProxyResolver::new(self.url.as_str())
+    }
+
+    /// A verifier trusting the issuers and resolving their keys from this server.
+    pub fn verifier<'a>(
+        &self,
+        trusted_issuers: impl IntoIterator<Item = DecentralizedIdentifier<'a>>,
+    ) -> VerifierBuilder<DidWebProxyResolver> {
+        Verifier::builder()
+            .trusted_issuers(trusted_issuers)
+            .resolver(self.resolver())
+    }
+}
+
+impl Drop for MockDidServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
@@ -0,0 +1,989 @@
+use std::{borrow::Cow, fmt, future::poll_fn, pin::pin, time::Duration};
+
+use futures_core::Stream;
+
+#[cfg(feature = "image")]
+use crate::payload::barcode::image::barcode_uris_from_image;
+use crate::{
+    batch::{PendingBatch, ResolvedKeys},
+    clock::{Clock, SystemClock},
+    decentralised_identifier::{
+        key_cache::KeyCache,
+        resolver::{BlockingKeyResolver, DidWebResolver, KeyResolver},
+        DecentralizedIdentifier, OwnedDecentralizedIdentifier,
+    },
+    error::NzcpError,
+    pass::{verify_pass, verify_pass_blocking, Credential, VerifiedPass, MINISTRY_OF_HEALTH_ISSUER},
+    payload::{
+        barcode::{Normalisation, QrBarcode},
+        cose::signature::{SignatureAlgorithm, NZCP_ALGORITHMS},
+        cwt::validation::Leeway,
+    },
+    report::{PendingReport, VerificationReport},
+};
+
+/// A pass verifier, configured once with a [`VerifierBuilder`] and then used to verify any number of passes.
+///
+/// ```ignore
+/// use nzcp::{PublicCovidPass, Verifier};
+///
+/// let verifier = Verifier::builder()
+///     .key_cache(Duration::from_secs(60 * 60), Duration::from_secs(60))
+///     .build();
+///
+/// let verified = verifier.verify::<PublicCovidPass>(barcode).await?;
+/// println!("{} is valid until {}", verified.pass.given_name, verified.expiry);
+/// ```
+pub struct Verifier<R = DidWebResolver> {
+    trusted_issuers: Vec<OwnedDecentralizedIdentifier>,
+    allowed_algorithms: Vec<SignatureAlgorithm>,
+    resolver: R,
+    clock: Box<dyn Clock>,
+    leeway: Leeway,
+    lenient_barcodes: bool,
+}
+
+impl Verifier {
+    /// A builder for a verifier, which by default trusts only the MoH `nzcp.identity.health.nz` issuer, resolves keys
+    /// online from the issuer's DID document and uses the system clock with no leeway.
+    pub fn builder() -> VerifierBuilder {
+        VerifierBuilder::default()
+    }
+}
+
+impl Default for Verifier {
+    fn default() -> Self {
+        Verifier::builder().build()
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for Verifier<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Verifier")
+            .field("trusted_issuers", &self.trusted_issuers)
+            .field("allowed_algorithms", &self.allowed_algorithms)
+            .field("resolver", &self.resolver)
+            .field("leeway", &self.leeway)
+            .field("lenient_barcodes", &self.lenient_barcodes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R: KeyResolver> Verifier<R> {
+    /// Verify a pass barcode URI (from a scanned QR code), returning the pass if verified or failing if not.
+    ///
+    /// Also returns the details of the CWT and the leeway which was needed to accept the pass, if any.
+    pub async fn verify<P: Credential>(&self, uri: &str) -> Result<VerifiedPass<P>, NzcpError> {
+        let trusted_issuers = self.trusted_issuers();
+        let (uri, normalisations) = self.normalise(uri);
+
+        let verified = verify_pass(
+            &uri,
+            &trusted_issuers,
+            &self.allowed_algorithms,
+            self.clock.as_ref(),
+            self.leeway,
+            &self.resolver,
+        )
+        .await?;
+        Ok(VerifiedPass {
+            normalisations,
+            ..verified
+        })
+    }
+
+    /// Make every verification check rather than stopping at the first failure, reporting the outcome of each check
+    /// to diagnose why a pass was rejected.
+    pub async fn report<P: Credential>(&self, uri: &str) -> VerificationReport {
+        let report = PendingReport::<P>::new(
+            &self.normalise(uri).0,
+            &self.trusted_issuers(),
+            &self.allowed_algorithms,
+            self.clock.now(),
+            self.leeway,
+        );
+        let verifying_key = match report.key_to_resolve() {
+            Some((issuer, kid)) => Some(self.resolver.resolve_verifying_key(issuer, kid).await),
+            None => None,
+        };
+
+        report.finish(verifying_key)
+    }
+
+    /// Verify many barcode URIs, returning the result for each in the order given.
+    ///
+    /// Each issuer key is resolved once for the whole batch, then signatures are verified in parallel across the
+    /// available cores on their own threads, so the async executor isn't blocked.
+    pub async fn verify_batch<P: Credential + Send + 'static>(
+        &self,
+        uris: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Vec<Result<VerifiedPass<P>, NzcpError>> {
+        let trusted_issuers = self.trusted_issuers();
+        let mut batch = self.batch(&trusted_issuers);
+        for uri in uris {
+            batch.push(uri.as_ref());
+        }
+
+        self.finish_batch(batch).await
+    }
+
+    /// Verify a stream of barcode URIs as a batch, returning the result for each in the order given. See
+    /// [`Verifier::verify_batch`].
+    ///
+    /// Barcodes are decoded as they arrive, and verified once the stream ends.
+    pub async fn verify_batch_stream<P: Credential + Send + 'static>(
+        &self,
+        uris: impl Stream<Item = impl AsRef<str>>,
+    ) -> Vec<Result<VerifiedPass<P>, NzcpError>> {
+        let trusted_issuers = self.trusted_issuers();
+        let mut batch = self.batch(&trusted_issuers);
+        let mut uris = pin!(uris);
+        while let Some(uri) = poll_fn(|context| uris.as_mut().poll_next(context)).await {
+            batch.push(uri.as_ref());
+        }
+
+        self.finish_batch(batch).await
+    }
+
+    async fn finish_batch<P: Credential + Send + 'static>(
+        &self,
+        batch: PendingBatch<'_, P>,
+    ) -> Vec<Result<VerifiedPass<P>, NzcpError>> {
+        let mut keys = ResolvedKeys::new();
+        for (issuer, kid) in batch.keys_to_resolve() {
+            let verifying_key = self.resolver.resolve_verifying_key(issuer, kid).await;
+            keys.insert((issuer.did(), kid.to_owned()), verifying_key);
+        }
+
+        batch.finish_in_background(keys).await
+    }
+
+    /// Verify the pass QR code in a PNG or JPEG photo or screenshot of a pass, returning the pass if verified or failing
+    /// if not.
+    ///
+    /// If the image has several NZCP QR codes the first found is verified; use [`barcode_uris_from_image`] to verify
+    /// each of them.
+    #[cfg(feature = "image")]
+    pub async fn verify_image<P: Credential>(&self, image: &[u8]) -> Result<VerifiedPass<P>, NzcpError> {
+        let uris = barcode_uris_from_image(image)?;
+
+        self.verify(&uris[0]).await
+    }
+}
+
+impl<R: BlockingKeyResolver> Verifier<R> {
+    /// Verify a pass barcode URI synchronously, without an async runtime, returning the pass if verified or failing if
+    /// not.
+    ///
+    /// Requires a resolver which doesn't need network access, such as a `TrustStore`.
+    pub fn verify_blocking<P: Credential>(&self, uri: &str) -> Result<VerifiedPass<P>, NzcpError> {
+        let trusted_issuers = self.trusted_issuers();
+        let (uri, normalisations) = self.normalise(uri);
+
+        let verified = verify_pass_blocking(
+            &uri,
+            &trusted_issuers,
+            &self.allowed_algorithms,
+            self.clock.as_ref(),
+            self.leeway,
+            &self.resolver,
+        )?;
+        Ok(VerifiedPass {
+            normalisations,
+            ..verified
+        })
+    }
+
+    /// Make every verification check synchronously, reporting the outcome of each check.
+    pub fn report_blocking<P: Credential>(&self, uri: &str) -> VerificationReport {
+        let report = PendingReport::<P>::new(
+            &self.normalise(uri).0,
+            &self.trusted_issuers(),
+            &self.allowed_algorithms,
+            self.clock.now(),
+            self.leeway,
+        );
+        let verifying_key = report
+            .key_to_resolve()
+            .map(|(issuer, kid)| self.resolver.resolve_verifying_key_blocking(issuer, kid));
+
+        report.finish(verifying_key)
+    }
+
+    /// Verify many barcode URIs synchronously, returning the result for each in the order given.
+    ///
+    /// Each issuer key is resolved once for the whole batch, then signatures are verified in parallel across the
+    /// available cores.
+    pub fn verify_batch_blocking<P: Credential + Send>(
+        &self,
+        uris: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Vec<Result<VerifiedPass<P>, NzcpError>> {
+        let trusted_issuers = self.trusted_issuers();
+        let mut batch = self.batch(&trusted_issuers);
+        for uri in uris {
+            batch.push(uri.as_ref());
+        }
+
+        let keys = batch
+            .keys_to_resolve()
+            .into_iter()
+            .map(|(issuer, kid)| {
+                let verifying_key = self.resolver.resolve_verifying_key_blocking(issuer, kid);
+                ((issuer.did(), kid.to_owned()), verifying_key)
+            })
+            .collect();
+
+        batch.finish(keys)
+    }
+
+    /// Verify the pass QR code in a PNG or JPEG image synchronously. See [`Verifier::verify_image`].
+    #[cfg(feature = "image")]
+    pub fn verify_image_blocking<P: Credential>(&self, image: &[u8]) -> Result<VerifiedPass<P>, NzcpError> {
+        let uris = barcode_uris_from_image(image)?;
+
+        self.verify_blocking(&uris[0])
+    }
+}
+
+impl<R> Verifier<R> {
+    /// The issuers whose passes are trusted.
+    pub fn trusted_issuers(&self) -> Vec<DecentralizedIdentifier<'_>> {
+        self.trusted_issuers
+            .iter()
+            .map(OwnedDecentralizedIdentifier::as_borrowed)
+            .collect()
+    }
+
+    /// The signature algorithms passes may be signed with, see [`VerifierBuilder::allowed_algorithms`].
+    pub fn allowed_algorithms(&self) -> &[SignatureAlgorithm] {
+        &self.allowed_algorithms
+    }
+
+    /// The resolver used to look up issuers' verifying keys.
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+
+    /// The clock skew tolerated in the not before and expiry dates.
+    pub fn leeway(&self) -> Leeway {
+        self.leeway
+    }
+
+    /// Whether barcodes are normalised before verifying, see [`VerifierBuilder::lenient_barcodes`].
+    pub fn lenient_barcodes(&self) -> bool {
+        self.lenient_barcodes
+    }
+
+    fn batch<'a, P: Credential>(&'a self, trusted_issuers: &'a [DecentralizedIdentifier<'a>]) -> PendingBatch<'a, P> {
+        PendingBatch::new(
+            trusted_issuers,
+            &self.allowed_algorithms,
+            self.lenient_barcodes,
+            self.clock.now(),
+            self.leeway,
+        )
+    }
+
+    fn normalise<'a>(&self, uri: &'a str) -> (Cow<'a, str>, Vec<Normalisation>) {
+        if self.lenient_barcodes {
+            QrBarcode::normalise(uri)
+        }
+        else {
+            (Cow::Borrowed(uri), Vec::new())
+        }
+    }
+}
+
+/// Builds a [`Verifier`].
+pub struct VerifierBuilder<R = DidWebResolver> {
+    trusted_issuers: Vec<OwnedDecentralizedIdentifier>,
+    allowed_algorithms: Vec<SignatureAlgorithm>,
+    resolver: R,
+    clock: Box<dyn Clock>,
+    leeway: Leeway,
+    lenient_barcodes: bool,
+}
+
+impl Default for VerifierBuilder {
+    fn default() -> Self {
+        VerifierBuilder {
+            trusted_issuers: vec![MINISTRY_OF_HEALTH_ISSUER.into()],
+            allowed_algorithms: NZCP_ALGORITHMS.to_vec(),
+            resolver: DidWebResolver,
+            clock: Box::new(SystemClock),
+            leeway: Leeway::default(),
+            lenient_barcodes: false,
+        }
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for VerifierBuilder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerifierBuilder")
+            .field("trusted_issuers", &self.trusted_issuers)
+            .field("allowed_algorithms", &self.allowed_algorithms)
+            .field("resolver", &self.resolver)
+            .field("leeway", &self.leeway)
+            .field("lenient_barcodes", &self.lenient_barcodes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R> VerifierBuilder<R> {
+    /// Trust only the given issuers, replacing the default MoH `nzcp.identity.health.nz` issuer.
+    pub fn trusted_issuers<'a>(mut self, issuers: impl IntoIterator<Item = DecentralizedIdentifier<'a>>) -> Self {
+        self.trusted_issuers = issuers.into_iter().map(OwnedDecentralizedIdentifier::from).collect();
+        self
+    }
+
+    /// Accept passes signed with the given algorithms, replacing the default of only ES256, which is the only algorithm
+    /// NZCP permits. Allow others only to verify passes from issuers outside NZCP which use the same format.
+    pub fn allowed_algorithms(mut self, algorithms: impl IntoIterator<Item = SignatureAlgorithm>) -> Self {
+        self.allowed_algorithms = algorithms.into_iter().collect();
+        self
+    }
+
+    /// Look up issuers' verifying keys with the given resolver (e.g. a `TrustStore` for offline verification).
+    pub fn resolver<R2>(self, resolver: R2) -> VerifierBuilder<R2> {
+        VerifierBuilder {
+            trusted_issuers: self.trusted_issuers,
+            allowed_algorithms: self.allowed_algorithms,
+            resolver,
+            clock: self.clock,
+            leeway: self.leeway,
+            lenient_barcodes: self.lenient_barcodes,
+        }
+    }
+
+    /// Cache the keys found by the current resolver, see [`KeyCache`].
+    pub fn key_cache(self, ttl: Duration, negative_ttl: Duration) -> VerifierBuilder<KeyCache<R>> {
+        let resolver = KeyCache::with_resolver(self.resolver, ttl, negative_ttl);
+        VerifierBuilder {
+            trusted_issuers: self.trusted_issuers,
+            allowed_algorithms: self.allowed_algorithms,
+            resolver,
+            clock: self.clock,
+            leeway: self.leeway,
+            lenient_barcodes: self.lenient_barcodes,
+        }
+    }
+
+    /// Check that passes are active and have not expired as at the time given by the clock.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Tolerate the given clock skew in the not before and expiry dates.
+    pub fn leeway(mut self, leeway: Leeway) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Accept barcodes mangled by scanners or copy and paste (surrounding whitespace, lower case and base32 padding),
+    /// reporting the changes needed in [`VerifiedPass::normalisations`]. By default barcodes must be spec-exact.
+    pub fn lenient_barcodes(mut self, lenient: bool) -> Self {
+        self.lenient_barcodes = lenient;
+        self
+    }
+
+    pub fn build(self) -> Verifier<R> {
+        Verifier {
+            trusted_issuers: self.trusted_issuers,
+            allowed_algorithms: self.allowed_algorithms,
+            resolver: self.resolver,
+            clock: self.clock,
+            leeway: self.leeway,
+            lenient_barcodes: self.lenient_barcodes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::{TimeZone, Utc};
+    use ssi::jwk::{Base64urlUInt, ECParams, OctetParams, Params, JWK};
+
+    use super::*;
+    use crate::{
+        error::{CoseVerificationError, CwtValidationError, DecentralizedIdentifierError, ErrorCode, QrBarcodeError},
+        payload::{
+            cose::{protected_headers::ProtectedHeaders, signature::sign::sign1_with, CoseStructure},
+            cwt::CwtClaims,
+            error::{Claim, PayloadError, ProtectedHeader},
+        },
+        AnyPass, Check, PublicCovidPass, RawPass, TrustStore, VerifyingKey,
+    };
+
+    // https://nzcp.covid19.health.nz/#valid-worked-example
+    const BARCODE: &str = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX";
+
+    // https://nzcp.covid19.health.nz/#expired-worked-example
+    const EXPIRED_BARCODE: &str = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUX5AM2FQIGTBPBPYWYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVA56TNJCCUN2NVK5NGAYOZ6VIWACYIBM3QXW7SLCMD2WTJ3GSEI5JH7RXAEURGATOHAHXC2O6BEJKBSVI25ICTBR5SFYUDSVLB2F6SJ63LWJ6Z3FWNHOXF6A2QLJNUFRQNTRU";
+
+    fn example_verifier() -> VerifierBuilder<TrustStore> {
+        let mut trust_store = TrustStore::new();
+        trust_store
+            .add_document_json(include_str!("../tests/did/nzcp.covid19.health.nz.json"))
+            .unwrap();
+
+        Verifier::builder()
+            .trusted_issuers([DecentralizedIdentifier::Web("nzcp.covid19.health.nz")])
+            .resolver(trust_store)
+    }
+
+    #[tokio::test]
+    async fn verify_many() {
+        let verifier = example_verifier()
+            .key_cache(Duration::from_secs(60), Duration::ZERO)
+            .build();
+
+        for _ in 0..2 {
+            let verified = verifier.verify::<PublicCovidPass>(BARCODE).await.unwrap();
+            assert_eq!(verified.pass.given_name, "Jack");
+            assert_eq!(verified.jti(), "urn:uuid:60a4f54d-4e30-4332-be33-ad78b1eafa4b");
+            assert_eq!(verified.key_reference(), "did:web:nzcp.covid19.health.nz#key-1");
+            assert_eq!(verified.not_before, Utc.timestamp(1635883530, 0));
+            assert_eq!(verified.expiry, Utc.timestamp(1951416330, 0));
+            assert_eq!(verified.version, "1.0.0");
+            assert!(!verified.applied_leeway.is_applied());
+        }
+    }
+
+    #[test]
+    fn verify_blocking() {
+        let verifier = example_verifier()
+            .key_cache(Duration::from_secs(60), Duration::ZERO)
+            .build();
+
+        let verified = verifier.verify_blocking::<PublicCovidPass>(BARCODE).unwrap();
+        assert_eq!(verified.pass.given_name, "Jack");
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn verify_image() {
+        use std::io::Cursor;
+
+        use image::{DynamicImage, ImageFormat, Luma};
+        use qrcode::QrCode;
+
+        use crate::error::QrImageError;
+
+        let png = |content: &str| {
+            let image = QrCode::new(content).unwrap().render::<Luma<u8>>().build();
+            let mut bytes = Vec::new();
+            DynamicImage::ImageLuma8(image)
+                .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+                .unwrap();
+            bytes
+        };
+        let verifier = example_verifier().build();
+
+        let verified = verifier
+            .verify_image_blocking::<PublicCovidPass>(&png(BARCODE))
+            .unwrap();
+        assert_eq!(verified.pass.given_name, "Jack");
+
+        assert_eq!(
+            verifier
+                .verify_image_blocking::<PublicCovidPass>(&png("https://example.com"))
+                .unwrap_err(),
+            NzcpError::QrImage(QrImageError::NoNzcpQrCode(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn untrusted_issuer() {
+        let verifier = example_verifier().trusted_issuers([MINISTRY_OF_HEALTH_ISSUER]).build();
+        assert_eq!(verifier.trusted_issuers(), vec![MINISTRY_OF_HEALTH_ISSUER]);
+
+        assert_eq!(
+            verifier.verify::<PublicCovidPass>(BARCODE).await.unwrap_err(),
+            NzcpError::InvalidSignature(CoseVerificationError::UntrustedIssuer(String::from(
+                "did:web:nzcp.covid19.health.nz"
+            )))
+        );
+    }
+
+    #[tokio::test]
+    async fn clock_and_leeway() {
+        let expiry = Utc.timestamp(1951416330, 0);
+        let expired = example_verifier().clock(expiry + chrono::Duration::seconds(30)).build();
+        assert!(matches!(
+            expired.verify::<PublicCovidPass>(BARCODE).await.unwrap_err(),
+            NzcpError::InvalidCWT(CwtValidationError::Expired(_))
+        ));
+
+        let lenient = example_verifier()
+            .clock(expiry + chrono::Duration::seconds(30))
+            .leeway(Leeway::new(chrono::Duration::zero(), chrono::Duration::seconds(60)).unwrap())
+            .build();
+        let verified = lenient.verify::<PublicCovidPass>(BARCODE).await.unwrap();
+        assert!(verified.applied_leeway.is_applied());
+    }
+
+    #[tokio::test]
+    async fn verify_any_pass() {
+        let verifier = example_verifier().build();
+
+        let verified = verifier.verify::<AnyPass>(BARCODE).await.unwrap();
+        assert!(matches!(verified.pass, AnyPass::PublicCovidPass(pass) if pass.given_name == "Jack"));
+        assert!(matches!(
+            verifier.verify::<AnyPass>(EXPIRED_BARCODE).await.unwrap_err(),
+            NzcpError::InvalidCWT(CwtValidationError::Expired(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn lenient_barcodes() {
+        let mangled = format!("{}\r\n", BARCODE.to_lowercase());
+
+        let verifier = example_verifier().build();
+        assert_eq!(
+            verifier.verify::<PublicCovidPass>(&mangled).await.unwrap_err(),
+            NzcpError::QrBarcode(QrBarcodeError::Lowercase(0))
+        );
+        assert_eq!(
+            verifier
+                .verify::<PublicCovidPass>(&format!("{}\r\n", BARCODE))
+                .await
+                .unwrap_err(),
+            NzcpError::QrBarcode(QrBarcodeError::Whitespace(BARCODE.len()))
+        );
+        assert!(verifier
+            .verify::<PublicCovidPass>(BARCODE)
+            .await
+            .unwrap()
+            .normalisations
+            .is_empty());
+
+        let verifier = example_verifier().lenient_barcodes(true).build();
+        let verified = verifier.verify::<PublicCovidPass>(&mangled).await.unwrap();
+        assert_eq!(verified.pass.given_name, "Jack");
+        assert_eq!(
+            verified.normalisations,
+            [Normalisation::Whitespace, Normalisation::Lowercase]
+        );
+
+        let results = verifier.verify_batch_blocking::<PublicCovidPass>([BARCODE, &mangled]);
+        assert!(results[0].as_ref().unwrap().normalisations.is_empty());
+        assert_eq!(results[1].as_ref().unwrap().normalisations.len(), 2);
+        assert!(verifier.report_blocking::<PublicCovidPass>(&mangled).is_valid());
+    }
+
+    #[tokio::test]
+    async fn verify_raw_pass() {
+        let verifier = example_verifier().build();
+        let verified = verifier.verify::<RawPass>(BARCODE).await.unwrap();
+
+        assert_eq!(verified.pass.credential_type, "PublicCovidPass");
+        assert_eq!(
+            verified.pass.context,
+            [
+                "https://www.w3.org/2018/credentials/v1",
+                "https://nzcp.covid19.health.nz/contexts/v1"
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&verified.pass.credential_subject).unwrap(),
+            serde_json::json!({"givenName": "Jack", "familyName": "Sparrow", "dob": "1960-04-16"})
+        );
+    }
+
+    #[tokio::test]
+    async fn verify_batch() {
+        let verifier = example_verifier().build();
+        let results = verifier
+            .verify_batch::<PublicCovidPass>([BARCODE, EXPIRED_BARCODE, "https://example.com", BARCODE])
+            .await;
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().pass.given_name, "Jack");
+        assert!(matches!(
+            results[1],
+            Err(NzcpError::InvalidCWT(CwtValidationError::Expired(_)))
+        ));
+        assert_eq!(
+            results[2].as_ref().unwrap_err(),
+            &NzcpError::QrBarcode(QrBarcodeError::MissingNzcpPrefix)
+        );
+        assert_eq!(results[3].as_ref().unwrap().pass, results[0].as_ref().unwrap().pass);
+
+        // an invalid signature is reported before an expired pass, as when verifying a single pass
+        let bad_signature = format!("{}A", &EXPIRED_BARCODE[..EXPIRED_BARCODE.len() - 1]);
+        let results = verifier.verify_batch::<PublicCovidPass>([&bad_signature]).await;
+        assert_eq!(
+            results[0].as_ref().unwrap_err(),
+            &verifier.verify::<PublicCovidPass>(&bad_signature).await.unwrap_err()
+        );
+        assert!(matches!(
+            results[0],
+            Err(NzcpError::InvalidSignature(CoseVerificationError::VerificationFailed))
+        ));
+    }
+
+    #[tokio::test]
+    async fn verify_batch_stream() {
+        let verifier = example_verifier().build();
+        let results = verifier
+            .verify_batch_stream::<PublicCovidPass>(futures_util::stream::iter([BARCODE, EXPIRED_BARCODE]))
+            .await;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().pass.given_name, "Jack");
+        assert!(matches!(
+            results[1],
+            Err(NzcpError::InvalidCWT(CwtValidationError::Expired(_)))
+        ));
+    }
+
+    #[derive(Default)]
+    struct CountingResolver {
+        trust_store: TrustStore,
+        resolutions: AtomicUsize,
+    }
+
+    impl BlockingKeyResolver for CountingResolver {
+        fn resolve_verifying_key_blocking(
+            &self,
+            issuer: DecentralizedIdentifier<'_>,
+            kid: &str,
+        ) -> Result<VerifyingKey, DecentralizedIdentifierError> {
+            self.resolutions.fetch_add(1, Ordering::SeqCst);
+            self.trust_store.resolve_verifying_key_blocking(issuer, kid)
+        }
+    }
+
+    #[test]
+    fn verify_batch_resolves_each_key_once() {
+        let mut resolver = CountingResolver::default();
+        resolver
+            .trust_store
+            .add_document_json(include_str!("../tests/did/nzcp.covid19.health.nz.json"))
+            .unwrap();
+        let verifier = example_verifier().resolver(resolver).build();
+
+        let results = verifier.verify_batch_blocking::<PublicCovidPass>(vec![BARCODE; 50]);
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(verifier.resolver().resolutions.load(Ordering::SeqCst), 1);
+
+        let untrusted = example_verifier()
+            .trusted_issuers([MINISTRY_OF_HEALTH_ISSUER])
+            .resolver(CountingResolver::default())
+            .build();
+        let results = untrusted.verify_batch_blocking::<PublicCovidPass>([BARCODE]);
+        assert!(matches!(
+            results[..],
+            [Err(NzcpError::InvalidSignature(
+                CoseVerificationError::UntrustedIssuer(_)
+            ))]
+        ));
+        assert_eq!(untrusted.resolver().resolutions.load(Ordering::SeqCst), 0);
+    }
+
+    const ALGORITHMS: [SignatureAlgorithm; 4] = [
+        SignatureAlgorithm::Es256,
+        SignatureAlgorithm::Es384,
+        SignatureAlgorithm::Es512,
+        SignatureAlgorithm::EdDsa,
+    ];
+
+    type Sign = Box<dyn Fn(&[u8]) -> Vec<u8>>;
+
+    /// A key for each algorithm, published in the example issuer's DID document under the algorithm's name.
+    fn signer(algorithm: SignatureAlgorithm) -> (VerifyingKey, Sign) {
+        use p256::ecdsa::signature::Signer as _;
+
+        match algorithm {
+            SignatureAlgorithm::Es256 => {
+                let key = p256::ecdsa::SigningKey::from_slice(&[1; 32]).unwrap();
+                let verifying_key = (*key.verifying_key()).into();
+                let sign = move |message: &[u8]| {
+                    let signature: p256::ecdsa::Signature = key.sign(message);
+                    signature.to_vec()
+                };
+                (verifying_key, Box::new(sign))
+            }
+            SignatureAlgorithm::Es384 => {
+                let key = p384::ecdsa::SigningKey::from_slice(&[1; 48]).unwrap();
+                let verifying_key = (*key.verifying_key()).into();
+                let sign = move |message: &[u8]| {
+                    let signature: p384::ecdsa::Signature = key.sign(message);
+                    signature.to_vec()
+                };
+                (verifying_key, Box::new(sign))
+            }
+            SignatureAlgorithm::Es512 => {
+                let key = p521::ecdsa::SigningKey::from_slice(&[1; 66]).unwrap();
+                let verifying_key = p521::ecdsa::VerifyingKey::from(&key).into();
+                let sign = move |message: &[u8]| {
+                    let signature: p521::ecdsa::Signature = key.sign(message);
+                    signature.to_vec()
+                };
+                (verifying_key, Box::new(sign))
+            }
+            SignatureAlgorithm::EdDsa => {
+                let key = ed25519_dalek::SigningKey::from_bytes(&[1; 32]);
+                let verifying_key = key.verifying_key().into();
+                let sign = move |message: &[u8]| key.sign(message).to_vec();
+                (verifying_key, Box::new(sign))
+            }
+        }
+    }
+
+    fn jwk(verifying_key: &VerifyingKey) -> JWK {
+        let bytes = verifying_key.to_bytes();
+        let ec = |curve: &str| {
+            let (x, y) = bytes[1..].split_at(bytes.len() / 2);
+            Params::EC(ECParams {
+                curve: Some(curve.to_owned()),
+                x_coordinate: Some(Base64urlUInt(x.to_vec())),
+                y_coordinate: Some(Base64urlUInt(y.to_vec())),
+                ecc_private_key: None,
+            })
+        };
+
+        JWK::from(match verifying_key.algorithm() {
+            SignatureAlgorithm::Es256 => ec("P-256"),
+            SignatureAlgorithm::Es384 => ec("P-384"),
+            SignatureAlgorithm::Es512 => ec("P-521"),
+            SignatureAlgorithm::EdDsa => Params::OKP(OctetParams {
+                curve: String::from("Ed25519"),
+                public_key: Base64urlUInt(bytes.clone()),
+                private_key: None,
+            }),
+        })
+    }
+
+    fn multi_algorithm_verifier() -> VerifierBuilder<TrustStore> {
+        let did = "did:web:nzcp.covid19.health.nz";
+        let key_id = |algorithm: &SignatureAlgorithm| format!("{}#{}", did, algorithm);
+        let document = serde_json::json!({
+            "id": did,
+            "@context": ["https://www.w3.org/ns/did/v1", "https://w3id.org/security/suites/jws-2020/v1"],
+            "verificationMethod": ALGORITHMS.iter().map(|algorithm| serde_json::json!({
+                "id": key_id(algorithm),
+                "controller": did,
+                "type": "JsonWebKey2020",
+                "publicKeyJwk": jwk(&signer(*algorithm).0),
+            })).collect::<Vec<_>>(),
+            "assertionMethod": ALGORITHMS.iter().map(key_id).collect::<Vec<_>>(),
+        });
+
+        let mut trust_store = TrustStore::new();
+        trust_store.add_document_json(&document.to_string()).unwrap();
+
+        Verifier::builder()
+            .trusted_issuers([DecentralizedIdentifier::Web("nzcp.covid19.health.nz")])
+            .resolver(trust_store)
+    }
+
+    /// A pass with the algorithm in its protected headers, signed with the key for `kid`.
+    fn issue(algorithm: SignatureAlgorithm, kid: SignatureAlgorithm) -> String {
+        let pass = PublicCovidPass {
+            given_name: String::from("Jack"),
+            family_name: Some(String::from("Sparrow")),
+            date_of_birth: chrono::NaiveDate::from_ymd(1960, 4, 16),
+        };
+        let kid = kid.to_string();
+        let protected_headers = ProtectedHeaders { kid: &kid, algorithm };
+        let now = Utc::now();
+        let cwt_claims = CwtClaims::new(
+            uuid::Uuid::nil(),
+            DecentralizedIdentifier::Web("nzcp.covid19.health.nz"),
+            now - chrono::Duration::days(1),
+            now + chrono::Duration::days(1),
+            &pass,
+        );
+
+        let cose = sign1_with(&protected_headers, &cwt_claims, signer(algorithm).1).unwrap();
+        QrBarcode::new(cose).to_string()
+    }
+
+    #[tokio::test]
+    async fn allowed_algorithms() {
+        let verifier = multi_algorithm_verifier().allowed_algorithms(ALGORITHMS).build();
+
+        for algorithm in ALGORITHMS {
+            let verified = verifier
+                .verify::<PublicCovidPass>(&issue(algorithm, algorithm))
+                .await
+                .unwrap();
+            assert_eq!(verified.pass.given_name, "Jack", "{}", algorithm);
+
+            let report = verifier.report::<PublicCovidPass>(&issue(algorithm, algorithm)).await;
+            assert!(report.is_valid(), "{}", algorithm);
+        }
+
+        let batch = verifier.verify_batch_blocking::<PublicCovidPass>(ALGORITHMS.map(|alg| issue(alg, alg)));
+        assert!(batch.iter().all(Result::is_ok));
+    }
+
+    #[tokio::test]
+    async fn only_es256_allowed_by_default() {
+        let verifier = multi_algorithm_verifier().build();
+        assert_eq!(verifier.allowed_algorithms(), [SignatureAlgorithm::Es256]);
+
+        assert!(verifier
+            .verify::<PublicCovidPass>(&issue(SignatureAlgorithm::Es256, SignatureAlgorithm::Es256))
+            .await
+            .is_ok());
+
+        for algorithm in [
+            SignatureAlgorithm::Es384,
+            SignatureAlgorithm::Es512,
+            SignatureAlgorithm::EdDsa,
+        ] {
+            let barcode = issue(algorithm, algorithm);
+
+            let error = verifier.verify::<PublicCovidPass>(&barcode).await.unwrap_err();
+            assert_eq!(
+                error,
+                NzcpError::InvalidSignature(CoseVerificationError::DisallowedAlgorithm(algorithm))
+            );
+            assert_eq!(error.code(), ErrorCode::UnsupportedAlgorithm);
+
+            let batch = verifier.verify_batch_blocking::<PublicCovidPass>([&barcode]);
+            assert_eq!(batch[0].as_ref().unwrap_err(), &error);
+
+            // the signature is still checked, to show it's only the algorithm at fault
+            let report = verifier.report_blocking::<PublicCovidPass>(&barcode);
+            let failures: Vec<_> = report.failures().map(|result| result.check).collect();
+            assert_eq!(failures, [Check::Algorithm]);
+            assert_eq!(
+                report.get(Check::Algorithm).unwrap().details,
+                format!("signature algorithm {} is not allowed, must be ES256 (-7)", algorithm)
+            );
+        }
+    }
+
+    #[test]
+    fn key_algorithm_mismatch() {
+        let verifier = multi_algorithm_verifier().allowed_algorithms(ALGORITHMS).build();
+
+        // signed with the P-256 key, but claiming to be ES384
+        let barcode = issue(SignatureAlgorithm::Es384, SignatureAlgorithm::Es256);
+        let error = verifier.verify_blocking::<PublicCovidPass>(&barcode).unwrap_err();
+        assert_eq!(
+            error,
+            NzcpError::InvalidSignature(CoseVerificationError::KeyAlgorithmMismatch {
+                algorithm: SignatureAlgorithm::Es384,
+                key_algorithm: SignatureAlgorithm::Es256,
+            })
+        );
+        assert_eq!(error.code(), ErrorCode::KeyAlgorithmMismatch);
+    }
+
+    type Map = std::collections::BTreeMap<serde_cbor::Value, serde_cbor::Value>;
+
+    /// A validly signed ES256 pass, with its protected headers and CWT claims changed by `tamper` before signing.
+    fn issue_tampered(tamper: impl FnOnce(&mut Map, &mut Map)) -> String {
+        use serde_bytes::Bytes;
+        use serde_cbor::tags::Tagged;
+
+        let barcode: QrBarcode = issue(SignatureAlgorithm::Es256, SignatureAlgorithm::Es256)
+            .parse()
+            .unwrap();
+        let cose = CoseStructure::<serde_cbor::Value>::from_slice(&barcode.cbor).unwrap();
+        let mut protected_headers: Map = serde_cbor::from_slice(cose.signature().protected_headers_raw).unwrap();
+        let mut cwt_claims: Map = serde_cbor::from_slice(cose.signature().cwt_claims_raw).unwrap();
+        tamper(&mut protected_headers, &mut cwt_claims);
+
+        let protected_headers = serde_cbor::to_vec(&protected_headers).unwrap();
+        let cwt_claims = serde_cbor::to_vec(&cwt_claims).unwrap();
+        let to_be_signed = serde_cbor::to_vec(&(
+            "Signature1",
+            Bytes::new(&protected_headers),
+            Bytes::new(&[]),
+            Bytes::new(&cwt_claims),
+        ))
+        .unwrap();
+        let signature = signer(SignatureAlgorithm::Es256).1(&to_be_signed);
+
+        let cose = serde_cbor::to_vec(&Tagged::new(
+            Some(18),
+            (
+                Bytes::new(&protected_headers),
+                Map::new(),
+                Bytes::new(&cwt_claims),
+                Bytes::new(&signature),
+            ),
+        ))
+        .unwrap();
+        QrBarcode::new(cose).to_string()
+    }
+
+    #[test]
+    fn report_agrees_with_verify() {
+        use serde_cbor::Value;
+
+        let verifier = multi_algorithm_verifier().build();
+
+        let untampered = issue_tampered(|_, _| {});
+        assert!(verifier.verify_blocking::<PublicCovidPass>(&untampered).is_ok());
+        assert!(verifier.report_blocking::<PublicCovidPass>(&untampered).is_valid());
+
+        let tampered = [
+            (
+                issue_tampered(|_, claims| {
+                    claims.remove(&Value::Integer(7));
+                }),
+                PayloadError::MissingClaim(Claim::CwtTokenId),
+                Check::CoseStructure,
+            ),
+            (
+                issue_tampered(|_, claims| {
+                    claims.insert(Value::Integer(7), Value::Bytes(vec![1, 2, 3]));
+                }),
+                PayloadError::InvalidCwtTokenId,
+                Check::CoseStructure,
+            ),
+            (
+                issue_tampered(|_, claims| {
+                    claims.insert(Value::Integer(6), Value::Integer(1635883530));
+                }),
+                PayloadError::UnknownClaim(String::from("6")),
+                Check::CoseStructure,
+            ),
+            (
+                issue_tampered(|headers, _| {
+                    headers.insert(Value::Integer(3), Value::Integer(60));
+                }),
+                PayloadError::UnknownProtectedHeader(String::from("3")),
+                Check::CoseStructure,
+            ),
+            (
+                issue_tampered(|_, claims| {
+                    claims.remove(&Value::Integer(1));
+                }),
+                PayloadError::MissingClaim(Claim::Issuer),
+                Check::Issuer,
+            ),
+            (
+                issue_tampered(|headers, _| {
+                    headers.remove(&Value::Integer(4));
+                }),
+                PayloadError::MissingProtectedHeader(ProtectedHeader::KeyId),
+                Check::KeyResolution,
+            ),
+        ];
+
+        for (barcode, error, check) in tampered {
+            let report = verifier.report_blocking::<PublicCovidPass>(&barcode);
+            assert!(!report.is_valid(), "{}", error);
+            assert_eq!(
+                report.failures().map(|result| result.check).collect::<Vec<_>>(),
+                [check]
+            );
+            assert_eq!(report.get(check).unwrap().details, error.to_string());
+
+            assert_eq!(
+                verifier.verify_blocking::<PublicCovidPass>(&barcode).unwrap_err(),
+                NzcpError::InvalidPayload(error)
+            );
+        }
+    }
+}
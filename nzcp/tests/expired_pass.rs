@@ -1,19 +1,25 @@
 use chrono::{DateTime, NaiveDateTime, Utc};
 use nzcp::{
     error::{CwtValidationError, NzcpError},
-    verify_pass_uri_with_trusted_issuers, DecentralizedIdentifier, PublicCovidPass,
+    verify_pass_uri_with_trusted_issuers_at, DecentralizedIdentifier, PublicCovidPass,
 };
 
 const EXAMPLE_ISSUER: DecentralizedIdentifier<'static> = DecentralizedIdentifier::Web("nzcp.covid19.health.nz");
 
+/// When the spec examples were published, so the result doesn't depend on the current time.
+fn verified_at() -> DateTime<Utc> {
+    DateTime::from_utc(NaiveDateTime::from_timestamp(1635897600, 0), Utc)
+}
+
 // https://nzcp.covid19.health.nz/#expired-pass
 #[tokio::test]
 async fn expired_pass() {
     let barcode = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUX5AM2FQIGTBPBPYWYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVA56TNJCCUN2NVK5NGAYOZ6VIWACYIBM3QXW7SLCMD2WTJ3GSEI5JH7RXAEURGATOHAHXC2O6BEJKBSVI25ICTBR5SFYUDSVLB2F6SJ63LWJ6Z3FWNHOXF6A2QLJNUFRQNTRU";
 
-    let error: NzcpError = verify_pass_uri_with_trusted_issuers::<PublicCovidPass>(barcode, &[EXAMPLE_ISSUER])
-        .await
-        .unwrap_err();
+    let error: NzcpError =
+        verify_pass_uri_with_trusted_issuers_at::<PublicCovidPass>(barcode, &[EXAMPLE_ISSUER], verified_at())
+            .await
+            .unwrap_err();
 
     assert_eq!(
         error,
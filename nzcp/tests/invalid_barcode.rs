@@ -1,5 +1,6 @@
 use nzcp::{
     error::{NzcpError, QrBarcodeError},
+    payload::barcode::QrBarcode,
     verify_pass_uri_with_trusted_issuers, DecentralizedIdentifier, PublicCovidPass,
 };
 
@@ -13,5 +14,11 @@ async fn invalid_barcode() {
         .await
         .unwrap_err();
 
-    assert_eq!(error, NzcpError::QrBarcode(QrBarcodeError::InvalidBase32))
+    assert_eq!(error, NzcpError::QrBarcode(QrBarcodeError::Lowercase(8)));
+
+    // upper case isn't enough, as `0`, `1`, `8` and `9` aren't base32
+    assert_eq!(
+        QrBarcode::parse_lenient(barcode).err(),
+        Some(QrBarcodeError::InvalidBase32Character(64, '0'))
+    );
 }
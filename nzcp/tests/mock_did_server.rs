@@ -0,0 +1,189 @@
+#![cfg(feature = "test-utils")]
+
+use chrono::{Duration, NaiveDate, Utc};
+use nzcp::{
+    error::{CoseVerificationError, CwtValidationError, DecentralizedIdentifierError, ErrorCategory, NzcpError},
+    test_utils::{MockDidServer, TestIssuer},
+    AnyPass, DecentralizedIdentifier, Pass, PublicCovidPass, RawPass,
+};
+use serde::{Deserialize, Serialize};
+
+fn pass() -> PublicCovidPass {
+    PublicCovidPass {
+        given_name: String::from("Jack"),
+        family_name: Some(String::from("Sparrow")),
+        date_of_birth: NaiveDate::from_ymd(1960, 4, 16),
+    }
+}
+
+#[tokio::test]
+async fn issue_and_verify() {
+    let issuer = TestIssuer::new("issuer.test");
+    let server = MockDidServer::start().await;
+    server.publish(&issuer);
+
+    let barcode = issuer.issue_valid_pass_uri(&pass());
+    let verifier = server.verifier([issuer.identifier()]).build();
+    let verified = verifier.verify::<PublicCovidPass>(&barcode).await.unwrap();
+
+    assert_eq!(verified.pass, pass());
+    assert_eq!(verified.key_reference(), "did:web:issuer.test#key-1");
+}
+
+#[tokio::test]
+async fn expired_pass() {
+    let issuer = TestIssuer::new("issuer.test");
+    let server = MockDidServer::start().await;
+    server.publish(&issuer);
+
+    let now = Utc::now();
+    let expiry = now - Duration::days(1);
+    let barcode = issuer.issue_pass_uri(&pass(), now - Duration::days(2), expiry);
+    let verifier = server.verifier([issuer.identifier()]).build();
+
+    assert!(matches!(
+        verifier.verify::<PublicCovidPass>(&barcode).await.unwrap_err(),
+        NzcpError::InvalidCWT(CwtValidationError::Expired(_))
+    ));
+}
+
+#[tokio::test]
+async fn key_rotation() {
+    let issuer = TestIssuer::new("issuer.test").with_key("key-2");
+    let rotated = TestIssuer::new("issuer.test");
+    let server = MockDidServer::start().await;
+    server.publish(&issuer);
+
+    let now = Utc::now();
+    let barcode = issuer.issue_pass_uri_with_key("key-2", &pass(), now - Duration::days(1), now + Duration::days(1));
+    let verifier = server.verifier([issuer.identifier()]).build();
+    assert!(verifier.verify::<PublicCovidPass>(&barcode).await.is_ok());
+
+    // once key-2 is removed from the DID document its passes are rejected
+    server.publish(&rotated);
+    assert_eq!(
+        verifier.verify::<PublicCovidPass>(&barcode).await.unwrap_err(),
+        NzcpError::InvalidSignature(CoseVerificationError::DecentralizedIdentifierResolution(
+            DecentralizedIdentifierError::MissingAssertionMethod(String::from("did:web:issuer.test#key-2"))
+        ))
+    );
+}
+
+#[tokio::test]
+async fn forged_pass() {
+    let issuer = TestIssuer::new("issuer.test");
+    // a forger claiming to be the issuer, with their own key-1
+    let forger = TestIssuer::new("issuer.test");
+    let server = MockDidServer::start().await;
+    server.publish(&issuer);
+
+    let barcode = forger.issue_valid_pass_uri(&pass());
+    let verifier = server.verifier([issuer.identifier()]).build();
+
+    assert_eq!(
+        verifier.verify::<PublicCovidPass>(&barcode).await.unwrap_err(),
+        NzcpError::InvalidSignature(CoseVerificationError::VerificationFailed)
+    );
+}
+
+#[tokio::test]
+async fn unpublished_document() {
+    let issuer = TestIssuer::new("issuer.test");
+    let server = MockDidServer::start().await;
+    server.publish(&issuer);
+    server.unpublish(issuer.identifier());
+
+    let barcode = issuer.issue_valid_pass_uri(&pass());
+    let verifier = server.verifier([issuer.identifier()]).build();
+    let error = verifier.verify::<PublicCovidPass>(&barcode).await.unwrap_err();
+
+    assert_eq!(error.category(), ErrorCategory::Network);
+}
+
+// https://nzcp.covid19.health.nz/#valid-worked-example, against the published example DID document
+#[tokio::test]
+async fn spec_example_offline() {
+    let barcode = "NZCP:/1/2KCEVIQEIVVWK6JNGEASNICZAEP2KALYDZSGSZB2O5SWEOTOPJRXALTDN53GSZBRHEXGQZLBNR2GQLTOPICRUYMBTIFAIGTUKBAAUYTWMOSGQQDDN5XHIZLYOSBHQJTIOR2HA4Z2F4XXO53XFZ3TGLTPOJTS6MRQGE4C6Y3SMVSGK3TUNFQWY4ZPOYYXQKTIOR2HA4Z2F4XW46TDOAXGG33WNFSDCOJONBSWC3DUNAXG46RPMNXW45DFPB2HGL3WGFTXMZLSONUW63TFGEXDALRQMR2HS4DFQJ2FMZLSNFTGSYLCNRSUG4TFMRSW45DJMFWG6UDVMJWGSY2DN53GSZCQMFZXG4LDOJSWIZLOORUWC3CTOVRGUZLDOSRWSZ3JOZSW4TTBNVSWISTBMNVWUZTBNVUWY6KOMFWWKZ2TOBQXE4TPO5RWI33CNIYTSNRQFUYDILJRGYDVAYFE6VGU4MCDGK7DHLLYWHVPUS2YIDJOA6Y524TD3AZRM263WTY2BE4DPKIF27WKF3UDNNVSVWRDYIYVJ65IRJJJ6Z25M2DO4YZLBHWFQGVQR5ZLIWEQJOZTS3IQ7JTNCFDX";
+    let example_issuer = DecentralizedIdentifier::Web("nzcp.covid19.health.nz");
+    let server = MockDidServer::start().await;
+    server.publish_document(example_issuer, include_str!("did/nzcp.covid19.health.nz.json"));
+
+    let verifier = server.verifier([example_issuer]).build();
+    let verified = verifier.verify::<PublicCovidPass>(barcode).await.unwrap();
+
+    assert_eq!(verified.pass, pass());
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct TravelPass {
+    #[serde(rename = "givenName")]
+    given_name: String,
+    destination: String,
+}
+
+impl Pass for TravelPass {
+    const CREDENTIAL_TYPE: &'static str = "TravelPass";
+
+    const CONTEXT_URL: &'static str = "https://issuer.test/contexts/v1";
+}
+
+#[tokio::test]
+async fn any_pass_type() {
+    let issuer = TestIssuer::new("issuer.test");
+    let server = MockDidServer::start().await;
+    server.publish(&issuer);
+    let verifier = server.verifier([issuer.identifier()]).build();
+
+    let barcode = issuer.issue_valid_pass_uri(&pass());
+    let verified = verifier.verify::<AnyPass>(&barcode).await.unwrap();
+    assert_eq!(verified.pass, AnyPass::PublicCovidPass(pass()));
+
+    // pass types which aren't known are reported with their subject, rather than failing
+    let travel_pass = TravelPass {
+        given_name: String::from("Jack"),
+        destination: String::from("Tortuga"),
+    };
+    let barcode = issuer.issue_valid_pass_uri(&travel_pass);
+    let verified = verifier.verify::<AnyPass>(&barcode).await.unwrap();
+    assert!(verified.pass.is_unknown());
+    assert_eq!(verified.pass.credential_type(), "TravelPass");
+
+    // whereas verifying as another single pass type fails
+    assert!(matches!(
+        verifier.verify::<PublicCovidPass>(&barcode).await.unwrap_err(),
+        NzcpError::InvalidPayload(_)
+    ));
+    assert_eq!(verifier.verify::<TravelPass>(&barcode).await.unwrap().pass, travel_pass);
+}
+
+#[tokio::test]
+async fn raw_pass_with_extra_fields() {
+    let issuer = TestIssuer::new("issuer.test");
+    let server = MockDidServer::start().await;
+    server.publish(&issuer);
+    let verifier = server.verifier([issuer.identifier()]).build();
+
+    let travel_pass = TravelPass {
+        given_name: String::from("Jack"),
+        destination: String::from("Tortuga"),
+    };
+    let barcode = issuer.issue_valid_pass_uri(&travel_pass);
+    let verified = verifier.verify::<RawPass>(&barcode).await.unwrap();
+
+    assert_eq!(verified.pass.credential_type, "TravelPass");
+    assert_eq!(verified.pass.context[1], "https://issuer.test/contexts/v1");
+    assert_eq!(
+        serde_json::to_value(&verified.pass).unwrap()["credentialSubject"],
+        serde_json::json!({"givenName": "Jack", "destination": "Tortuga"})
+    );
+
+    // a forged raw pass is still rejected
+    let forger = TestIssuer::new("issuer.test");
+    assert_eq!(
+        verifier
+            .verify::<RawPass>(&forger.issue_valid_pass_uri(&travel_pass))
+            .await
+            .unwrap_err(),
+        NzcpError::InvalidSignature(CoseVerificationError::VerificationFailed)
+    );
+}